@@ -4,13 +4,16 @@ use std::{
     ops::RangeInclusive,
 };
 
+use interval_set::IntervalSet;
+
 type Pair = [RangeInclusive<i32>; 2];
 
-fn count_contains(pairs: &Vec<Pair>) -> i32 {
+fn count_contains(pairs: &[Pair]) -> i32 {
     pairs.iter().fold(0, |acc, pair| {
-        if pair[0].contains(&pair[1].start()) && pair[0].contains(&pair[1].end())
-            || pair[1].contains(&pair[0].start()) && pair[1].contains(&pair[0].end())
-        {
+        let [first, second] = pair.clone().map(IntervalSet::from);
+        let union = first.union(&second);
+
+        if union == first || union == second {
             acc + 1
         } else {
             acc
@@ -18,13 +21,11 @@ fn count_contains(pairs: &Vec<Pair>) -> i32 {
     })
 }
 
-fn count_intersects(pairs: &Vec<Pair>) -> i32 {
+fn count_intersects(pairs: &[Pair]) -> i32 {
     pairs.iter().fold(0, |acc, pair| {
-        if pair[0].contains(&pair[1].start())
-            || pair[0].contains(&pair[1].end())
-            || pair[1].contains(&pair[0].start())
-            || pair[1].contains(&pair[0].end())
-        {
+        let [first, second] = pair.clone().map(IntervalSet::from);
+
+        if !first.intersection(&second).is_empty() {
             acc + 1
         } else {
             acc
@@ -1,5 +1,11 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
 use std::fs::read_to_string;
+use std::io;
+use std::process;
+use std::str::FromStr;
 
 #[derive(Debug)]
 
@@ -8,26 +14,129 @@ enum Direction {
     Down,
     Left,
     Right,
+    UpLeft,
+    UpRight,
+    DownLeft,
+    DownRight,
 }
 
-impl Direction {
-    fn from(s: &str) -> Direction {
+#[derive(Debug)]
+enum MotionError {
+    UnknownDirection(String),
+    MissingDistance,
+    InvalidDistance(String),
+    UnexpectedToken(String),
+}
+
+impl Display for MotionError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            MotionError::UnknownDirection(s) => write!(
+                f,
+                "unknown direction {s:?}, expected one of U, D, L, R, UL, UR, DL or DR"
+            ),
+            MotionError::MissingDistance => write!(f, "missing distance"),
+            MotionError::InvalidDistance(s) => {
+                write!(f, "invalid distance {s:?}, expected a non-negative number")
+            }
+            MotionError::UnexpectedToken(s) => write!(f, "unexpected {s:?} after the distance"),
+        }
+    }
+}
+
+impl Error for MotionError {}
+
+#[derive(Debug)]
+enum LoadError {
+    Io(String, io::Error),
+    Motion { line: usize, error: MotionError },
+}
+
+impl Display for LoadError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::Io(file_name, error) => write!(f, "couldn't read {file_name}: {error}"),
+            LoadError::Motion { line, error } => {
+                write!(f, "invalid motion on line {line}: {error}")
+            }
+        }
+    }
+}
+
+impl Error for LoadError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            LoadError::Io(_, error) => Some(error),
+            LoadError::Motion { error, .. } => Some(error),
+        }
+    }
+}
+
+impl FromStr for Direction {
+    type Err = MotionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
-            "U" => Direction::Up,
-            "D" => Direction::Down,
-            "L" => Direction::Left,
-            "R" => Direction::Right,
-            _ => panic!("Could not parse direction: {s:?}"),
+            "U" => Ok(Direction::Up),
+            "D" => Ok(Direction::Down),
+            "L" => Ok(Direction::Left),
+            "R" => Ok(Direction::Right),
+            "UL" => Ok(Direction::UpLeft),
+            "UR" => Ok(Direction::UpRight),
+            "DL" => Ok(Direction::DownLeft),
+            "DR" => Ok(Direction::DownRight),
+            _ => Err(MotionError::UnknownDirection(s.to_owned())),
         }
     }
 }
 
+impl Display for Direction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let letter = match self {
+            Direction::Up => "U",
+            Direction::Down => "D",
+            Direction::Left => "L",
+            Direction::Right => "R",
+            Direction::UpLeft => "UL",
+            Direction::UpRight => "UR",
+            Direction::DownLeft => "DL",
+            Direction::DownRight => "DR",
+        };
+        write!(f, "{letter}")
+    }
+}
+
 #[derive(Debug)]
 struct Motion {
     direction: Direction,
     distance: i32,
 }
 
+impl FromStr for Motion {
+    type Err = MotionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut tokens = s.split_whitespace();
+
+        let direction = tokens.next().unwrap_or_default().parse()?;
+        let distance = tokens.next().ok_or(MotionError::MissingDistance)?;
+        let distance = distance
+            .parse()
+            .ok()
+            .filter(|distance| *distance >= 0)
+            .ok_or_else(|| MotionError::InvalidDistance(distance.to_owned()))?;
+
+        if let Some(token) = tokens.next() {
+            return Err(MotionError::UnexpectedToken(token.to_owned()));
+        }
+
+        Ok(Motion {
+            direction,
+            distance,
+        })
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
 struct Position {
     x: i32,
@@ -40,29 +149,51 @@ impl Position {
     }
 }
 
-fn load_motions() -> Vec<Motion> {
-    let file = read_to_string("input.txt").unwrap();
-    let lines: Vec<&str> = file.split('\n').collect();
+fn load_motions(file_name: &str) -> Result<Vec<Motion>, LoadError> {
+    let file =
+        read_to_string(file_name).map_err(|error| LoadError::Io(file_name.to_owned(), error))?;
 
-    lines
-        .iter()
-        .map(|&line| {
-            let tokens: Vec<&str> = line.split(' ').collect();
-
-            Motion {
-                direction: Direction::from(tokens[0]),
-                distance: tokens[1].parse().unwrap(),
-            }
+    file.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| {
+            line.parse().map_err(|error| LoadError::Motion {
+                line: index + 1,
+                error,
+            })
         })
         .collect()
 }
 
+fn load_motions_or_exit(file_name: &str) -> Vec<Motion> {
+    load_motions(file_name).unwrap_or_else(|error| {
+        eprintln!("{error}");
+        process::exit(1);
+    })
+}
+
 fn apply_step_to_head(direction: &Direction, head: &mut Position) {
     match direction {
         Direction::Up => head.y += 1,
         Direction::Down => head.y -= 1,
         Direction::Left => head.x -= 1,
         Direction::Right => head.x += 1,
+        Direction::UpLeft => {
+            head.y += 1;
+            head.x -= 1;
+        }
+        Direction::UpRight => {
+            head.y += 1;
+            head.x += 1;
+        }
+        Direction::DownLeft => {
+            head.y -= 1;
+            head.x -= 1;
+        }
+        Direction::DownRight => {
+            head.y -= 1;
+            head.x += 1;
+        }
     }
 }
 
@@ -92,45 +223,407 @@ fn apply_step_to_follower(leader: &Position, follower: &mut Position) {
     }
 }
 
-fn part_1(motions: &[Motion]) {
-    let mut rope_head = Position::default();
-    let mut rope_tail = Position::default();
-    let mut visited: HashMap<Position, bool> = HashMap::new();
+struct Rope {
+    knots: Vec<Position>,
+    visited: Vec<HashSet<Position>>,
+}
 
-    motions.iter().for_each(|motion| {
+impl Rope {
+    fn new(knot_count: usize) -> Rope {
+        if knot_count == 0 {
+            panic!("A rope needs at least one knot");
+        }
+
+        Rope {
+            knots: vec![Position::default(); knot_count],
+            visited: vec![HashSet::from([Position::default()]); knot_count],
+        }
+    }
+
+    fn step(&mut self, direction: &Direction) -> &[Position] {
+        apply_step_to_head(direction, &mut self.knots[0]);
+
+        (0..(self.knots.len() - 1)).for_each(|i| {
+            let leader = &self.knots[i].clone();
+            let follower = &mut self.knots[i + 1];
+            apply_step_to_follower(leader, follower);
+        });
+
+        self.knots
+            .iter()
+            .zip(self.visited.iter_mut())
+            .for_each(|(knot, visited)| {
+                visited.insert(*knot);
+            });
+
+        &self.knots
+    }
+
+    fn apply(&mut self, motion: &Motion) {
         (0..motion.distance).for_each(|_| {
-            apply_step_to_head(&motion.direction, &mut rope_head);
-            apply_step_to_follower(&rope_head, &mut rope_tail);
-            visited.insert(rope_tail, true);
+            self.step(&motion.direction);
         });
-    });
+    }
+
+    fn visited(&self, knot: usize) -> &HashSet<Position> {
+        &self.visited[knot]
+    }
+
+    // The smallest box holding every cell any knot has been in, the start included.
+    fn bounds(&self) -> (Position, Position) {
+        let cells: Vec<&Position> = self.visited.iter().flatten().collect();
+        let min = Position {
+            x: cells.iter().map(|cell| cell.x).min().unwrap(),
+            y: cells.iter().map(|cell| cell.y).min().unwrap(),
+        };
+        let max = Position {
+            x: cells.iter().map(|cell| cell.x).max().unwrap(),
+            y: cells.iter().map(|cell| cell.y).max().unwrap(),
+        };
+        (min, max)
+    }
+
+    fn label(&self, knot: usize) -> char {
+        match knot {
+            0 => 'H',
+            _ if self.knots.len() == 2 || knot > 9 => 'T',
+            _ => char::from_digit(knot as u32, 10).unwrap(),
+        }
+    }
+
+    // Rows run from the highest y down, like the puzzle's diagrams.
+    fn draw(cells: &HashMap<Position, char>, (min, max): (Position, Position)) -> String {
+        (min.y..=max.y)
+            .rev()
+            .map(|y| {
+                (min.x..=max.x)
+                    .map(|x| *cells.get(&Position { x, y }).unwrap_or(&'.'))
+                    .collect::<String>()
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
 
-    println!("Part 1: {:?}", visited.len());
+    // Earlier knots are drawn over later ones, and every knot over the start.
+    fn render(&self, bounds: (Position, Position)) -> String {
+        let mut cells = HashMap::from([(Position::default(), 's')]);
+        self.knots.iter().enumerate().rev().for_each(|(i, knot)| {
+            cells.insert(*knot, self.label(i));
+        });
+        Rope::draw(&cells, bounds)
+    }
+
+    fn render_trail(&self, bounds: (Position, Position)) -> String {
+        let mut cells: HashMap<Position, char> = self
+            .visited
+            .last()
+            .unwrap()
+            .iter()
+            .map(|cell| (*cell, '#'))
+            .collect();
+        cells.insert(Position::default(), 's');
+        Rope::draw(&cells, bounds)
+    }
 }
 
-fn part_2(motions: &[Motion]) {
-    let mut knots: [Position; 10] = [Position::default(); 10];
-    let mut visited: HashMap<Position, bool> = HashMap::new();
+#[derive(Debug, Clone, Copy)]
+enum FrameMode {
+    PerMotion,
+    PerStep,
+}
+
+// Every frame is cropped to the area the rope covers over the whole simulation so that they
+// line up, and the last one shows the tail's trail.
+fn render_frames(motions: &[Motion], knot_count: usize, mode: FrameMode) -> Vec<String> {
+    let mut finished = Rope::new(knot_count);
+    motions.iter().for_each(|motion| finished.apply(motion));
+    let bounds = finished.bounds();
+
+    let mut rope = Rope::new(knot_count);
+    let mut frames = vec![format!("== Initial State ==\n{}", rope.render(bounds))];
 
     motions.iter().for_each(|motion| {
-        (0..motion.distance).for_each(|_| {
-            apply_step_to_head(&motion.direction, &mut knots[0]);
+        let title = format!("== {} {} ==", motion.direction, motion.distance);
 
-            (0..(knots.len() - 1)).for_each(|i| {
-                let leader = &knots[i].clone();
-                let follower = &mut knots[i + 1];
-                apply_step_to_follower(leader, follower);
-            });
+        match mode {
+            FrameMode::PerMotion => {
+                rope.apply(motion);
+                frames.push(format!("{title}\n{}", rope.render(bounds)));
+            }
+            FrameMode::PerStep => (0..motion.distance).for_each(|step| {
+                rope.step(&motion.direction);
+                let frame = rope.render(bounds);
 
-            visited.insert(*knots.last().unwrap(), true);
-        });
+                if step == 0 {
+                    frames.push(format!("{title}\n{frame}"));
+                } else {
+                    frames.push(frame);
+                }
+            }),
+        }
     });
 
-    println!("Part 2: {:?}", visited.len());
+    frames.push(format!("== Trail ==\n{}", rope.render_trail(bounds)));
+    frames
+}
+
+fn visited_count(motions: &[Motion], knot_count: usize, knot: usize) -> usize {
+    let mut rope = Rope::new(knot_count);
+    motions.iter().for_each(|motion| rope.apply(motion));
+    rope.visited(knot).len()
+}
+
+fn part_1(motions: &[Motion]) {
+    println!("Part 1: {:?}", visited_count(motions, 2, 1));
+}
+
+fn part_2(motions: &[Motion]) {
+    println!("Part 2: {:?}", visited_count(motions, 10, 9));
 }
 
 fn main() {
-    let motions: Vec<Motion> = load_motions();
-    part_1(&motions);
-    part_2(&motions);
+    let args: Vec<String> = env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+
+    match args.as_slice() {
+        ["render", file_name, knot_count, mode] => {
+            let knot_count = knot_count
+                .parse()
+                .ok()
+                .filter(|knot_count| *knot_count > 0)
+                .unwrap_or_else(|| {
+                    eprintln!("invalid knot count {knot_count:?}, expected a positive number");
+                    process::exit(1);
+                });
+            let mode = match *mode {
+                "motion" => FrameMode::PerMotion,
+                "step" => FrameMode::PerStep,
+                _ => {
+                    eprintln!("unknown frame mode {mode:?}, expected motion or step");
+                    process::exit(1);
+                }
+            };
+            let motions = load_motions_or_exit(file_name);
+
+            render_frames(&motions, knot_count, mode)
+                .iter()
+                .for_each(|frame| println!("{frame}\n"));
+        }
+        _ => {
+            let motions: Vec<Motion> = load_motions_or_exit("input.txt");
+            part_1(&motions);
+            part_2(&motions);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        apply_step_to_follower, apply_step_to_head, load_motions, render_frames, visited_count,
+        Direction, FrameMode, LoadError, Motion, MotionError, Position, Rope,
+    };
+    use std::collections::HashSet;
+    use std::env;
+    use std::fs::write;
+
+    fn motions(text: &str) -> Vec<Motion> {
+        text.lines().map(|line| line.parse().unwrap()).collect()
+    }
+
+    fn cells(positions: &[(i32, i32)]) -> HashSet<Position> {
+        positions.iter().map(|&(x, y)| Position { x, y }).collect()
+    }
+
+    #[test]
+    fn step_moves_every_knot() {
+        let mut rope = Rope::new(3);
+        rope.step(&Direction::Right);
+        rope.step(&Direction::Right);
+        rope.step(&Direction::Up);
+
+        assert_eq!(
+            rope.step(&Direction::Up),
+            [
+                Position { x: 2, y: 2 },
+                Position { x: 2, y: 1 },
+                Position { x: 1, y: 1 },
+            ]
+        );
+        assert_eq!(
+            *rope.visited(0),
+            cells(&[(0, 0), (1, 0), (2, 0), (2, 1), (2, 2)])
+        );
+        assert_eq!(*rope.visited(1), cells(&[(0, 0), (1, 0), (2, 1)]));
+        assert_eq!(*rope.visited(2), cells(&[(0, 0), (1, 1)]));
+    }
+
+    #[test]
+    fn example_visited_counts() {
+        let motions = load_motions("example.txt").unwrap();
+
+        assert_eq!(visited_count(&motions, 2, 1), 13);
+        assert_eq!(visited_count(&motions, 10, 9), 1);
+    }
+
+    #[test]
+    fn larger_example_visited_counts() {
+        let motions = motions("R 5\nU 8\nL 8\nD 3\nR 17\nD 10\nL 25\nU 20");
+
+        assert_eq!(visited_count(&motions, 10, 9), 36);
+        assert_eq!(
+            visited_count(&motions, 5, 4),
+            visited_count(&motions, 10, 4)
+        );
+    }
+
+    #[test]
+    fn single_knot_rope() {
+        let motions = motions("R 4\nU 4\nL 3\nD 1");
+        let mut rope = Rope::new(1);
+
+        assert_eq!(rope.step(&Direction::Left), [Position { x: -1, y: 0 }]);
+        // A lone knot is its own tail, so it visits every cell the head moves through.
+        assert_eq!(visited_count(&motions, 1, 0), 13);
+    }
+
+    #[test]
+    fn example_frames_per_motion() {
+        let motions = load_motions("example.txt").unwrap();
+        let frames = render_frames(&motions, 2, FrameMode::PerMotion);
+
+        let expected = [
+            ("Initial State", "......\n......\n......\n......\nH....."),
+            ("R 4", "......\n......\n......\n......\ns..TH."),
+            ("U 4", "....H.\n....T.\n......\n......\ns....."),
+            ("L 3", ".HT...\n......\n......\n......\ns....."),
+            ("D 1", "..T...\n.H....\n......\n......\ns....."),
+            ("R 4", "......\n....TH\n......\n......\ns....."),
+            ("D 1", "......\n....T.\n.....H\n......\ns....."),
+            ("L 5", "......\n......\nHT....\n......\ns....."),
+            ("R 2", "......\n......\n.TH...\n......\ns....."),
+            ("Trail", "..##..\n...##.\n.####.\n....#.\ns###.."),
+        ]
+        .map(|(title, grid)| format!("== {title} ==\n{grid}"));
+
+        assert_eq!(frames, expected);
+    }
+
+    #[test]
+    fn example_frames_per_step() {
+        let motions = load_motions("example.txt").unwrap();
+        let frames = render_frames(&motions, 2, FrameMode::PerStep);
+
+        assert_eq!(frames.len(), 26);
+        assert_eq!(
+            frames[1],
+            "== R 4 ==\n......\n......\n......\n......\nTH...."
+        );
+        assert_eq!(frames[2], "......\n......\n......\n......\nsTH...");
+    }
+
+    #[test]
+    fn parse_directions() {
+        let directions: Vec<Direction> = ["U", "D", "L", "R", "UL", "UR", "DL", "DR"]
+            .iter()
+            .map(|letters| letters.parse().unwrap())
+            .collect();
+        let letters: Vec<String> = directions.iter().map(Direction::to_string).collect();
+
+        assert_eq!(letters, ["U", "D", "L", "R", "UL", "UR", "DL", "DR"]);
+        assert!(matches!(
+            "X".parse::<Direction>(),
+            Err(MotionError::UnknownDirection(letters)) if letters == "X"
+        ));
+    }
+
+    #[test]
+    fn parse_motions() {
+        let motion: Motion = "  DR   12 ".parse().unwrap();
+        assert!(matches!(motion.direction, Direction::DownRight));
+        assert_eq!(motion.distance, 12);
+
+        assert!(matches!(
+            "Q 3".parse::<Motion>(),
+            Err(MotionError::UnknownDirection(letters)) if letters == "Q"
+        ));
+        assert!(matches!(
+            "".parse::<Motion>(),
+            Err(MotionError::UnknownDirection(letters)) if letters.is_empty()
+        ));
+        assert!(matches!(
+            "U".parse::<Motion>(),
+            Err(MotionError::MissingDistance)
+        ));
+        assert!(matches!(
+            "U x".parse::<Motion>(),
+            Err(MotionError::InvalidDistance(distance)) if distance == "x"
+        ));
+        assert!(matches!(
+            "U -1".parse::<Motion>(),
+            Err(MotionError::InvalidDistance(distance)) if distance == "-1"
+        ));
+        assert!(matches!(
+            "U 1 2".parse::<Motion>(),
+            Err(MotionError::UnexpectedToken(token)) if token == "2"
+        ));
+    }
+
+    #[test]
+    fn load_errors_count_blank_lines() {
+        let file_name = env::temp_dir().join("day_09_blank_lines.txt");
+        let file_name = file_name.to_str().unwrap();
+
+        write(file_name, "R 4\n\n  \nU 2\n").unwrap();
+        assert_eq!(load_motions(file_name).unwrap().len(), 2);
+
+        write(file_name, "R 4\n\n  \nU two\n").unwrap();
+        let error = load_motions(file_name).unwrap_err();
+        assert!(matches!(
+            error,
+            LoadError::Motion {
+                line: 4,
+                error: MotionError::InvalidDistance(_)
+            }
+        ));
+        assert_eq!(
+            error.to_string(),
+            "invalid motion on line 4: invalid distance \"two\", expected a non-negative number"
+        );
+
+        assert!(matches!(
+            load_motions("missing.txt"),
+            Err(LoadError::Io(_, _))
+        ));
+    }
+
+    #[test]
+    fn diagonal_steps() {
+        let directions = [
+            (Direction::UpLeft, (-1, 1)),
+            (Direction::UpRight, (1, 1)),
+            (Direction::DownLeft, (-1, -1)),
+            (Direction::DownRight, (1, -1)),
+        ];
+        directions.iter().for_each(|(direction, (x, y))| {
+            let mut head = Position::default();
+            apply_step_to_head(direction, &mut head);
+            assert_eq!(head, Position { x: *x, y: *y });
+        });
+
+        let mut follower = Position::default();
+        apply_step_to_follower(&Position { x: 2, y: 2 }, &mut follower);
+        assert_eq!(follower, Position { x: 1, y: 1 });
+
+        let mut rope = Rope::new(2);
+        rope.step(&Direction::UpRight);
+        assert_eq!(rope.knots[1], Position::default());
+        rope.step(&Direction::UpRight);
+        rope.step(&Direction::DownRight);
+        assert_eq!(
+            rope.step(&Direction::DownRight),
+            [Position { x: 4, y: 0 }, Position { x: 3, y: 0 }]
+        );
+        assert_eq!(rope.visited(1).len(), 4);
+    }
 }
@@ -3,9 +3,10 @@ use anyhow::{anyhow, Result};
 use std::{
     collections::{BinaryHeap, HashMap},
     fmt::Debug,
+    time::Instant,
 };
 
-use crate::utils::read_input_lines;
+use crate::utils::{read_input_lines, rng::Rng};
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 struct Elevation(u16);
@@ -77,32 +78,40 @@ struct Heightmap {
 }
 
 fn load_heightmap() -> Result<Heightmap> {
-    let lines = read_input_lines("day_12");
-    let width = lines.first().unwrap().len();
-    let chars: Vec<char> = lines.concat().chars().collect();
+    Heightmap::from_lines(&read_input_lines("day_12"))
+}
 
-    let elevations: Vec<Elevation> = chars
-        .iter()
-        .map(|&char| Elevation::try_from(&char))
-        .collect::<Result<Vec<Elevation>>>()?;
+impl Heightmap {
+    fn from_lines(lines: &[String]) -> Result<Heightmap> {
+        let width = lines
+            .first()
+            .ok_or_else(|| anyhow!("Heightmap is empty"))?
+            .len();
+        let chars: Vec<char> = lines.concat().chars().collect();
+
+        let elevations: Vec<Elevation> = chars
+            .iter()
+            .map(|&char| Elevation::try_from(&char))
+            .collect::<Result<Vec<Elevation>>>()?;
 
-    let char_grid = Grid::new(chars, width);
+        let char_grid = Grid::new(chars, width);
 
-    let elevation_grid = Grid::new(elevations, width);
+        let elevation_grid = Grid::new(elevations, width);
 
-    let start_position = char_grid
-        .find(&START_CHAR)
-        .ok_or_else(|| anyhow!("Couldn't find start position"))?;
+        let start_position = char_grid
+            .find(&START_CHAR)
+            .ok_or_else(|| anyhow!("Couldn't find start position"))?;
 
-    let end_position = char_grid
-        .find(&END_CHAR)
-        .ok_or_else(|| anyhow!("Couldn't find end position"))?;
+        let end_position = char_grid
+            .find(&END_CHAR)
+            .ok_or_else(|| anyhow!("Couldn't find end position"))?;
 
-    Ok(Heightmap {
-        elevations: elevation_grid,
-        start: start_position,
-        end: end_position,
-    })
+        Ok(Heightmap {
+            elevations: elevation_grid,
+            start: start_position,
+            end: end_position,
+        })
+    }
 }
 
 #[derive(Debug)]
@@ -171,6 +180,129 @@ impl Heightmap {
     }
 }
 
+const MAX_ELEVATION: u16 = 26;
+
+impl From<Elevation> for char {
+    fn from(value: Elevation) -> Self {
+        (b'a' + (value.0 - 1) as u8) as char
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct HeightmapGenerator {
+    pub width: usize,
+    pub height: usize,
+    pub seed: u64,
+    pub reachable: bool,
+}
+
+impl HeightmapGenerator {
+    // Carves a random spanning tree over the cells (recursive backtracker), then ramps the
+    // elevation from `a` to `z` along the tree path between S and E. Every other cell is random,
+    // so the ramp guarantees a path exists. For unreachable maps, every neighbor of E is pulled
+    // down to at most `x`, which makes E impossible to climb onto.
+    pub fn generate(&self) -> Result<Vec<String>> {
+        let min_path_steps = (MAX_ELEVATION - 1) as usize;
+
+        if self.width * self.height <= min_path_steps {
+            return Err(anyhow!(
+                "A {}x{} heightmap is too small to climb from a to z",
+                self.width,
+                self.height
+            ));
+        }
+
+        let mut rng = Rng::new(self.seed);
+
+        let elevations: Vec<Elevation> = (0..self.width * self.height)
+            .map(|_| Elevation(rng.gen_range(1..(MAX_ELEVATION as usize + 1)) as u16))
+            .collect();
+        let mut elevations = Grid::new(elevations, self.width);
+
+        let start = Position::from((rng.gen_range(0..self.height), rng.gen_range(0..self.width)));
+        let mut depths = Grid::create_and_fill(self.height, self.width, usize::MAX);
+        let mut parents: Grid<Option<Position>> =
+            Grid::create_and_fill(self.height, self.width, None);
+        let mut stack = vec![start];
+        *depths.get_mut(&start).unwrap() = 0;
+
+        while let Some(&current) = stack.last() {
+            let unvisited: Vec<Position> = depths
+                .neighbors(&current)
+                .into_iter()
+                .filter(|neighbor| *neighbor.item == usize::MAX)
+                .map(|neighbor| neighbor.position)
+                .collect();
+
+            if unvisited.is_empty() {
+                stack.pop();
+                continue;
+            }
+
+            let next = unvisited[rng.gen_range(0..unvisited.len())];
+            let depth = depths.get(&current).unwrap() + 1;
+            *depths.get_mut(&next).unwrap() = depth;
+            *parents.get_mut(&next).unwrap() = Some(current);
+            stack.push(next);
+        }
+
+        let candidates: Vec<Position> = (0..self.height)
+            .flat_map(|row| (0..self.width).map(move |col| Position { row, col }))
+            .filter(|position| *depths.get(position).unwrap() >= min_path_steps)
+            .collect();
+
+        if candidates.is_empty() {
+            return Err(anyhow!(
+                "Couldn't find an end position far enough from the start"
+            ));
+        }
+
+        let end = candidates[rng.gen_range(0..candidates.len())];
+
+        let mut path = vec![end];
+        while let Some(parent) = parents.get(path.last().unwrap()).unwrap() {
+            path.push(*parent);
+        }
+        path.reverse();
+
+        let steps = path.len() - 1;
+        for (i, position) in path.iter().enumerate() {
+            let elevation = 1 + (i * min_path_steps / steps) as u16;
+            *elevations.get_mut(position).unwrap() = Elevation(elevation);
+        }
+
+        if !self.reachable {
+            for neighbor in elevations
+                .neighbors(&end)
+                .into_iter()
+                .map(|neighbor| neighbor.position)
+                .collect::<Vec<Position>>()
+            {
+                let elevation = rng.gen_range(1..(MAX_ELEVATION as usize - 1)) as u16;
+                *elevations.get_mut(&neighbor).unwrap() = Elevation(elevation);
+            }
+        }
+
+        let mut chars = Grid::new(
+            elevations
+                .rows()
+                .concat()
+                .into_iter()
+                .map(char::from)
+                .collect(),
+            self.width,
+        );
+        *chars.get_mut(&start).unwrap() = START_CHAR;
+        *chars.get_mut(&end).unwrap() = END_CHAR;
+
+        Ok(chars
+            .rows()
+            .iter()
+            .map(|row| row.iter().collect())
+            .collect())
+    }
+}
+
 pub fn day_12() -> Result<()> {
     let heightmap = load_heightmap()?;
     let dist = heightmap.dist_bfs(&heightmap.start, &heightmap.end);
@@ -178,6 +310,21 @@ pub fn day_12() -> Result<()> {
     Ok(())
 }
 
+pub fn bench_day_12(generator: HeightmapGenerator) -> Result<()> {
+    let lines = generator.generate()?;
+    let heightmap = Heightmap::from_lines(&lines)?;
+
+    let now = Instant::now();
+    let dist = heightmap.dist_bfs(&heightmap.start, &heightmap.end);
+    let elapsed = now.elapsed();
+
+    println!(
+        "{}x{} (seed {}): {dist:?} in {elapsed:?}",
+        generator.width, generator.height, generator.seed
+    );
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -203,4 +350,74 @@ mod tests {
         assert_eq!(dist, Some(414));
         Ok(())
     }
+
+    #[test]
+    fn generated_heightmap_is_reproducible() -> Result<()> {
+        let generator = HeightmapGenerator {
+            width: 30,
+            height: 20,
+            seed: 12,
+            reachable: true,
+        };
+        assert_eq!(generator.generate()?, generator.generate()?);
+        Ok(())
+    }
+
+    #[test]
+    fn generated_heightmap_has_path() -> Result<()> {
+        for seed in 0..20 {
+            let generator = HeightmapGenerator {
+                width: 40,
+                height: 25,
+                seed,
+                reachable: true,
+            };
+            let heightmap = Heightmap::from_lines(&generator.generate()?)?;
+            let dist = heightmap.dist_bfs(&heightmap.start, &heightmap.end);
+            assert!(dist.is_some(), "seed {seed} should have a path");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn generated_heightmap_has_no_path() -> Result<()> {
+        for seed in 0..20 {
+            let generator = HeightmapGenerator {
+                width: 40,
+                height: 25,
+                seed,
+                reachable: false,
+            };
+            let heightmap = Heightmap::from_lines(&generator.generate()?)?;
+            let dist = heightmap.dist_bfs(&heightmap.start, &heightmap.end);
+            assert_eq!(dist, None, "seed {seed} should not have a path");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn large_generated_heightmap() -> Result<()> {
+        let generator = HeightmapGenerator {
+            width: 300,
+            height: 300,
+            seed: 1,
+            reachable: true,
+        };
+        let heightmap = Heightmap::from_lines(&generator.generate()?)?;
+        assert!(heightmap
+            .dist_bfs(&heightmap.start, &heightmap.end)
+            .is_some());
+        Ok(())
+    }
+
+    #[test]
+    fn too_small_heightmap() {
+        let generator = HeightmapGenerator {
+            width: 5,
+            height: 5,
+            seed: 0,
+            reachable: true,
+        };
+        assert!(generator.generate().is_err());
+    }
 }
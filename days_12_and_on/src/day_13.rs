@@ -1,90 +1,504 @@
-use std::{cmp::Ordering, iter::zip};
+use std::{cmp::Ordering, fmt::Display, iter::zip, str::FromStr};
 
-use crate::utils::read_input_lines;
+use anyhow::{anyhow, Context, Result};
 
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub struct NestedArray(serde_json::Value);
+use crate::utils::{read_input_lines, rng::Rng};
 
-impl PartialOrd for NestedArray {
-    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        let (left, right) = (&self.0, &other.0);
+#[derive(Debug, Clone)]
+pub enum Packet {
+    Int(u64),
+    List(Vec<Packet>),
+}
+
+impl TryFrom<&serde_json::Value> for Packet {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &serde_json::Value) -> Result<Self, Self::Error> {
+        match value {
+            serde_json::Value::Number(number) => number
+                .as_u64()
+                .map(Packet::Int)
+                .ok_or_else(|| anyhow!("Expected a non-negative integer, found {number}")),
+            serde_json::Value::Array(items) => Ok(Packet::List(
+                items
+                    .iter()
+                    .map(Packet::try_from)
+                    .collect::<Result<Vec<Packet>>>()?,
+            )),
+            other => Err(anyhow!("Expected an integer or a list, found {other}")),
+        }
+    }
+}
+
+impl FromStr for Packet {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let value: serde_json::Value = serde_json::from_str(s)?;
+        Packet::try_from(&value)
+    }
+}
+
+impl Ord for Packet {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (Packet::Int(left), Packet::Int(right)) => left.cmp(right),
+            (Packet::List(left), Packet::List(right)) => left.cmp(right),
+            (Packet::Int(left), Packet::List(right)) => [Packet::Int(*left)][..].cmp(right),
+            (Packet::List(left), Packet::Int(right)) => left[..].cmp(&[Packet::Int(*right)]),
+        }
+    }
+}
 
-        if left.is_number() && right.is_number() {
-            let (left, right) = (left.as_i64()?, right.as_i64()?);
-            return left.partial_cmp(&right);
+impl PartialOrd for Packet {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// Equality follows the puzzle's ordering, so `2`, `[2]` and `[[2]]` are all equal.
+impl PartialEq for Packet {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Packet {}
+
+impl Display for Packet {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Packet::Int(value) => write!(f, "{value}"),
+            Packet::List(items) => {
+                f.write_str("[")?;
+                for (index, item) in items.iter().enumerate() {
+                    if index > 0 {
+                        f.write_str(",")?;
+                    }
+                    write!(f, "{item}")?;
+                }
+                f.write_str("]")
+            }
         }
+    }
+}
 
-        let left = if left.is_array() {
-            left.as_array()?.clone()
-        } else {
-            vec![left.clone()]
+impl Packet {
+    // Same result as `cmp`, plus the step-by-step explanation in the puzzle statement's wording.
+    pub fn cmp_with_trace(&self, other: &Packet) -> (Ordering, String) {
+        let mut trace: Vec<String> = vec![];
+        let ordering = self.cmp_traced(other, 0, &mut trace);
+        (ordering, trace.join("\n"))
+    }
+
+    fn cmp_traced(&self, other: &Packet, depth: usize, trace: &mut Vec<String>) -> Ordering {
+        let note = |trace: &mut Vec<String>, message: String| {
+            trace.push(format!("{}- {message}", "  ".repeat(depth + 1)))
         };
 
-        let right = if right.is_array() {
-            right.as_array()?.clone()
-        } else {
-            vec![right.clone()]
+        trace.push(format!("{}- Compare {self} vs {other}", "  ".repeat(depth)));
+
+        match (self, other) {
+            (Packet::Int(left), Packet::Int(right)) => {
+                let ordering = left.cmp(right);
+                match ordering {
+                    Ordering::Less => note(
+                        trace,
+                        "Left side is smaller, so inputs are in the right order".to_owned(),
+                    ),
+                    Ordering::Greater => note(
+                        trace,
+                        "Right side is smaller, so inputs are not in the right order".to_owned(),
+                    ),
+                    Ordering::Equal => {}
+                }
+                ordering
+            }
+            (Packet::List(left), Packet::List(right)) => {
+                for (left, right) in zip(left, right) {
+                    let ordering = left.cmp_traced(right, depth + 1, trace);
+                    if ordering != Ordering::Equal {
+                        return ordering;
+                    }
+                }
+
+                let ordering = left.len().cmp(&right.len());
+                match ordering {
+                    Ordering::Less => note(
+                        trace,
+                        "Left side ran out of items, so inputs are in the right order".to_owned(),
+                    ),
+                    Ordering::Greater => note(
+                        trace,
+                        "Right side ran out of items, so inputs are not in the right order"
+                            .to_owned(),
+                    ),
+                    Ordering::Equal => {}
+                }
+                ordering
+            }
+            (Packet::Int(value), Packet::List(_)) => {
+                let converted = Packet::List(vec![Packet::Int(*value)]);
+                note(
+                    trace,
+                    format!("Mixed types; convert left to {converted} and retry comparison"),
+                );
+                converted.cmp_traced(other, depth + 1, trace)
+            }
+            (Packet::List(_), Packet::Int(value)) => {
+                let converted = Packet::List(vec![Packet::Int(*value)]);
+                note(
+                    trace,
+                    format!("Mixed types; convert right to {converted} and retry comparison"),
+                );
+                self.cmp_traced(&converted, depth + 1, trace)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Token {
+    Open,
+    Close,
+    Comma,
+    Int(u64),
+}
+
+struct Tokenizer<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Tokenizer<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, position: 0 }
+    }
+
+    fn read_int(&mut self) -> Result<u64> {
+        let start = self.position;
+        let mut value: u64 = 0;
+
+        while let Some(&byte) = self.bytes.get(self.position) {
+            if !byte.is_ascii_digit() {
+                break;
+            }
+
+            value = value
+                .checked_mul(10)
+                .and_then(|value| value.checked_add((byte - b'0') as u64))
+                .ok_or_else(|| anyhow!("Integer at byte {start} is too large"))?;
+            self.position += 1;
+        }
+
+        Ok(value)
+    }
+}
+
+impl<'a> Iterator for Tokenizer<'a> {
+    type Item = Result<Token>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let &byte = self.bytes.get(self.position)?;
+
+        let token = match byte {
+            b',' => Token::Comma,
+            b'[' => Token::Open,
+            b']' => Token::Close,
+            b'0'..=b'9' => return Some(self.read_int().map(Token::Int)),
+            _ => {
+                return Some(Err(anyhow!(
+                    "Unexpected {:?} at byte {}",
+                    byte as char,
+                    self.position
+                )))
+            }
         };
 
-        for (left, right) in zip(left.clone(), right.clone()) {
-            let result = NestedArray(left.clone()).partial_cmp(&NestedArray(right.clone()))?;
-            if result != Ordering::Equal {
-                return Some(result);
+        self.position += 1;
+        Some(Ok(token))
+    }
+}
+
+// A tokenizer plus the tokens injected when an integer gets promoted to a single-item list.
+struct PacketStream<'a> {
+    tokenizer: Tokenizer<'a>,
+    pending: Vec<Token>,
+}
+
+impl<'a> PacketStream<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self {
+            tokenizer: Tokenizer::new(bytes),
+            pending: vec![],
+        }
+    }
+
+    // Commas only matter when validating, so comparisons never see them.
+    fn next(&mut self) -> Result<Option<Token>> {
+        if let Some(token) = self.pending.pop() {
+            return Ok(Some(token));
+        }
+
+        loop {
+            match self.tokenizer.next().transpose()? {
+                Some(Token::Comma) => {}
+                token => return Ok(token),
+            }
+        }
+    }
+
+    // The other side just opened a list, so this integer behaves as `[value]` whose `[` has
+    // already been consumed.
+    fn promote(&mut self, value: u64) {
+        self.pending.push(Token::Close);
+        self.pending.push(Token::Int(value));
+    }
+}
+
+// Commas are skipped rather than checked, so both packets must already have been validated by
+// `RawPacket::new`.
+fn compare_packets(left: &[u8], right: &[u8]) -> Result<Ordering> {
+    let mut left = PacketStream::new(left);
+    let mut right = PacketStream::new(right);
+
+    loop {
+        match (left.next()?, right.next()?) {
+            (None, None) => return Ok(Ordering::Equal),
+            (Some(Token::Int(left)), Some(Token::Int(right))) => {
+                if left != right {
+                    return Ok(left.cmp(&right));
+                }
             }
+            (Some(Token::Open), Some(Token::Open)) | (Some(Token::Close), Some(Token::Close)) => {}
+            (Some(Token::Close), Some(_)) => return Ok(Ordering::Less),
+            (Some(_), Some(Token::Close)) => return Ok(Ordering::Greater),
+            (Some(Token::Int(value)), Some(Token::Open)) => left.promote(value),
+            (Some(Token::Open), Some(Token::Int(value))) => right.promote(value),
+            _ => return Err(anyhow!("Packets ended unexpectedly")),
         }
+    }
+}
+
+// A packet compared straight from its source text, without building a `Packet` tree.
+#[derive(Debug, Clone, Copy)]
+pub struct RawPacket<'a>(&'a [u8]);
+
+impl<'a> RawPacket<'a> {
+    pub fn new(s: &'a str) -> Result<Self> {
+        let mut depth: usize = 0;
+        let mut values: usize = 0;
+        let mut previous: Option<Token> = None;
 
-        if left.len() < right.len() {
-            return Some(Ordering::Less);
+        for token in Tokenizer::new(s.as_bytes()) {
+            let token = token?;
+
+            match (previous, token) {
+                (None | Some(Token::Open | Token::Comma), Token::Comma)
+                | (Some(Token::Comma), Token::Close) => {
+                    return Err(anyhow!("Misplaced ',' in {s:?}"))
+                }
+                (_, Token::Comma) if depth == 0 => return Err(anyhow!("Misplaced ',' in {s:?}")),
+                (Some(Token::Int(_) | Token::Close), Token::Int(_) | Token::Open) if depth > 0 => {
+                    return Err(anyhow!("Missing ',' in {s:?}"))
+                }
+                _ => {}
+            }
+
+            match token {
+                Token::Open => depth += 1,
+                Token::Close => {
+                    depth = depth
+                        .checked_sub(1)
+                        .ok_or_else(|| anyhow!("Unbalanced ']' in {s:?}"))?
+                }
+                Token::Comma | Token::Int(_) => {}
+            }
+
+            if depth == 0 && token != Token::Comma {
+                values += 1;
+            }
+
+            previous = Some(token);
         }
 
-        if left.len() > right.len() {
-            return Some(Ordering::Greater);
+        if depth != 0 {
+            return Err(anyhow!("Unclosed '[' in {s:?}"));
         }
 
-        Some(Ordering::Equal)
+        if values != 1 {
+            return Err(anyhow!("Expected exactly one value in {s:?}"));
+        }
+
+        Ok(Self(s.as_bytes()))
     }
 }
 
-impl Ord for NestedArray {
+impl<'a> Ord for RawPacket<'a> {
     fn cmp(&self, other: &Self) -> Ordering {
-        self.partial_cmp(other).unwrap()
+        compare_packets(self.0, other.0).expect("RawPacket is validated on construction")
     }
 }
 
-const DIVIDER_PACKET_1: &str = "[[2]]";
-const DIVIDER_PACKET_2: &str = "[[6]]";
+impl<'a> PartialOrd for RawPacket<'a> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
 
-pub fn load_input() -> Vec<(NestedArray, NestedArray)> {
-    let input_lines = read_input_lines("day_13");
+impl<'a> PartialEq for RawPacket<'a> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<'a> Eq for RawPacket<'a> {}
+
+#[derive(Debug, Clone, Copy)]
+pub struct PacketGenerator {
+    pub max_depth: usize,
+    pub max_len: usize,
+    pub max_int: u64,
+}
+
+impl PacketGenerator {
+    pub fn generate(&self, rng: &mut Rng) -> Packet {
+        self.generate_at_depth(rng, 0)
+    }
+
+    fn generate_at_depth(&self, rng: &mut Rng, depth: usize) -> Packet {
+        if depth >= self.max_depth || (depth > 0 && rng.gen_bool(0.4)) {
+            return Packet::Int(rng.next_u64() % (self.max_int + 1));
+        }
+
+        let len = rng.gen_range(0..(self.max_len + 1));
+        Packet::List(
+            (0..len)
+                .map(|_| self.generate_at_depth(rng, depth + 1))
+                .collect(),
+        )
+    }
+}
+
+pub const DIVIDER_PACKETS: [&str; 2] = ["[[2]]", "[[6]]"];
+
+pub fn parse_packets(packets: &[&str]) -> Result<Vec<Packet>> {
+    packets.iter().map(|packet| packet.parse()).collect()
+}
 
-    let input_lines: Vec<serde_json::Value> = input_lines
+// 1-based position each marker would have if the markers were added to `packets` and
+// everything was sorted. Equal markers are ordered as given.
+pub fn marker_ranks(packets: &[Packet], markers: &[Packet]) -> Vec<usize> {
+    markers
         .iter()
-        .filter_map(|input_line| serde_json::from_str(input_line).ok())
-        .collect();
+        .enumerate()
+        .map(|(index, marker)| {
+            let lesser_packets = packets.iter().filter(|packet| *packet < marker).count();
+            let lesser_markers = markers
+                .iter()
+                .enumerate()
+                .filter(|(other_index, other)| match (*other).cmp(marker) {
+                    Ordering::Less => true,
+                    Ordering::Equal => *other_index < index,
+                    Ordering::Greater => false,
+                })
+                .count();
 
-    input_lines
-        .chunks(2)
-        .map(|chunk| (NestedArray(chunk[0].clone()), NestedArray(chunk[1].clone())))
+            lesser_packets + lesser_markers + 1
+        })
         .collect()
 }
 
-pub fn load_input_part_2() -> Vec<NestedArray> {
-    let input_lines = read_input_lines("day_13");
+pub fn decoder_key(packets: &[Packet], markers: &[Packet]) -> usize {
+    marker_ranks(packets, markers).iter().product()
+}
 
-    let mut input_lines: Vec<serde_json::Value> = input_lines
-        .iter()
-        .filter_map(|input_line| serde_json::from_str(input_line).ok())
-        .collect();
+#[derive(Debug, Default, Clone)]
+pub struct SortedPackets {
+    packets: Vec<Packet>,
+}
+
+impl SortedPackets {
+    // Returns the 1-based rank the packet was inserted at, after any equal packets.
+    pub fn insert(&mut self, packet: Packet) -> usize {
+        let index = self.packets.partition_point(|other| *other <= packet);
+        self.packets.insert(index, packet);
+        index + 1
+    }
+
+    // 1-based rank of the first packet equal to `packet`, if there is one.
+    pub fn rank(&self, packet: &Packet) -> Option<usize> {
+        let index = self.packets.partition_point(|other| other < packet);
+        self.packets
+            .get(index)
+            .filter(|other| *other == packet)
+            .map(|_| index + 1)
+    }
+
+    // Number of packets strictly less than `packet`.
+    pub fn count_less(&self, packet: &Packet) -> usize {
+        self.packets.partition_point(|other| other < packet)
+    }
+
+    pub fn get(&self, rank: usize) -> Option<&Packet> {
+        self.packets.get(rank.checked_sub(1)?)
+    }
+
+    pub fn len(&self) -> usize {
+        self.packets.len()
+    }
 
-    input_lines.push(serde_json::from_str(DIVIDER_PACKET_1).unwrap());
-    input_lines.push(serde_json::from_str(DIVIDER_PACKET_2).unwrap());
+    pub fn is_empty(&self) -> bool {
+        self.packets.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Packet> {
+        self.packets.iter()
+    }
+}
 
-    input_lines
+impl FromIterator<Packet> for SortedPackets {
+    fn from_iter<I: IntoIterator<Item = Packet>>(iter: I) -> Self {
+        let mut packets: Vec<Packet> = iter.into_iter().collect();
+        packets.sort();
+        Self { packets }
+    }
+}
+
+fn load_packets(file_name: &str) -> Result<Vec<Packet>> {
+    read_input_lines(file_name)
         .iter()
-        .map(|value| NestedArray(value.clone()))
+        .enumerate()
+        .filter(|(_, line)| !line.is_empty())
+        .map(|(index, line)| {
+            line.parse::<Packet>()
+                .with_context(|| format!("Invalid packet on line {}", index + 1))
+        })
         .collect()
 }
 
+pub fn load_input() -> Result<Vec<(Packet, Packet)>> {
+    let packets = load_packets("day_13")?;
+
+    if packets.len() % 2 != 0 {
+        return Err(anyhow!(
+            "Expected packets in pairs, found {}",
+            packets.len()
+        ));
+    }
+
+    Ok(packets
+        .chunks(2)
+        .map(|chunk| (chunk[0].clone(), chunk[1].clone()))
+        .collect())
+}
+
+pub fn load_input_part_2(markers: &[Packet]) -> Result<Vec<Packet>> {
+    let mut packets = load_packets("day_13")?;
+    packets.extend_from_slice(markers);
+    Ok(packets)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -93,7 +507,7 @@ mod tests {
 
     #[test]
     fn part_1() -> Result<()> {
-        let input_lines = load_input();
+        let input_lines = load_input()?;
 
         let result: usize = input_lines
             .par_iter()
@@ -116,18 +530,297 @@ mod tests {
 
     #[test]
     fn part_2() -> Result<()> {
-        let mut lines = load_input_part_2();
+        let dividers = parse_packets(&DIVIDER_PACKETS)?;
+        let mut lines = load_input_part_2(&dividers)?;
 
         lines.par_sort_unstable();
 
-        let divider_1: serde_json::Value = serde_json::from_str(DIVIDER_PACKET_1).unwrap();
-        let divider_2: serde_json::Value = serde_json::from_str(DIVIDER_PACKET_2).unwrap();
+        let (divider_1, divider_2) = (&dividers[0], &dividers[1]);
 
-        let index_1 = lines.iter().position(|item| item.0 == divider_1).unwrap() + 1;
-        let index_2 = lines.iter().position(|item| item.0 == divider_2).unwrap() + 1;
+        let index_1 = lines.iter().position(|item| item == divider_1).unwrap() + 1;
+        let index_2 = lines.iter().position(|item| item == divider_2).unwrap() + 1;
 
         assert_eq!(index_1 * index_2, 24948);
 
         Ok(())
     }
+
+    #[test]
+    fn part_2_marker_ranks() -> Result<()> {
+        let packets = load_packets("day_13")?;
+        let dividers = parse_packets(&DIVIDER_PACKETS)?;
+        assert_eq!(decoder_key(&packets, &dividers), 24948);
+        Ok(())
+    }
+
+    #[test]
+    fn marker_ranks_match_sorted_packets() -> Result<()> {
+        let packets = load_packets("day_13")?;
+        let markers = parse_packets(&["[[2]]", "[5,[1]]", "[]", "[[6]]", "[[[9]]]"])?;
+
+        let sorted: SortedPackets = packets.iter().chain(&markers).cloned().collect();
+        let expected: Vec<usize> = markers
+            .iter()
+            .map(|marker| sorted.rank(marker).unwrap())
+            .collect();
+
+        assert_eq!(marker_ranks(&packets, &markers), expected);
+        Ok(())
+    }
+
+    #[test]
+    fn sorted_packets_insert() -> Result<()> {
+        let mut sorted = SortedPackets::default();
+        for packet in parse_packets(&["[3]", "[1,2]", "[[4]]", "[]"])? {
+            sorted.insert(packet);
+        }
+
+        assert_eq!(sorted.insert("[2]".parse()?), 3);
+        assert_eq!(sorted.rank(&"[[2]]".parse()?), Some(3));
+        assert_eq!(sorted.rank(&"[5]".parse()?), None);
+        assert_eq!(sorted.count_less(&"[5]".parse()?), 5);
+
+        let sorted: Vec<String> = sorted.iter().map(|packet| packet.to_string()).collect();
+        assert_eq!(sorted, ["[]", "[1,2]", "[2]", "[3]", "[[4]]"]);
+        Ok(())
+    }
+
+    #[test]
+    fn mixed_types() -> Result<()> {
+        let left: Packet = "[[1],[2,3,4]]".parse()?;
+        let right: Packet = "[[1],4]".parse()?;
+        assert!(left < right);
+
+        let left: Packet = "[9]".parse()?;
+        let right: Packet = "[[8,7,6]]".parse()?;
+        assert!(left > right);
+
+        let left: Packet = "[[2]]".parse()?;
+        let right: Packet = "2".parse()?;
+        assert_eq!(left, right);
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_invalid_packets() {
+        assert!("[1,\"a\"]".parse::<Packet>().is_err());
+        assert!("[1.5]".parse::<Packet>().is_err());
+        assert!("[-1]".parse::<Packet>().is_err());
+        assert!("{\"a\":[1]}".parse::<Packet>().is_err());
+        assert!("[1,[2]".parse::<Packet>().is_err());
+    }
+
+    #[test]
+    fn raw_packets_match_packets() -> Result<()> {
+        let lines = read_input_lines("day_13");
+        let lines: Vec<&String> = lines.iter().filter(|line| !line.is_empty()).collect();
+
+        for left in lines.iter().take(40) {
+            for right in lines.iter() {
+                let expected = left.parse::<Packet>()?.cmp(&right.parse::<Packet>()?);
+                assert_eq!(RawPacket::new(left)?.cmp(&RawPacket::new(right)?), expected);
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn part_2_raw() -> Result<()> {
+        let lines = read_input_lines("day_13");
+        let mut packets = lines
+            .iter()
+            .filter(|line| !line.is_empty())
+            .map(|line| RawPacket::new(line))
+            .collect::<Result<Vec<RawPacket>>>()?;
+
+        let divider_1 = RawPacket::new(DIVIDER_PACKETS[0])?;
+        let divider_2 = RawPacket::new(DIVIDER_PACKETS[1])?;
+        packets.push(divider_1);
+        packets.push(divider_2);
+        packets.sort_unstable();
+
+        let index_1 = packets.iter().position(|item| *item == divider_1).unwrap() + 1;
+        let index_2 = packets.iter().position(|item| *item == divider_2).unwrap() + 1;
+
+        assert_eq!(index_1 * index_2, 24948);
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_invalid_raw_packets() {
+        assert!(RawPacket::new("[1,[2]").is_err());
+        assert!(RawPacket::new("[1]]").is_err());
+        assert!(RawPacket::new("[1][2]").is_err());
+        assert!(RawPacket::new("[1,a]").is_err());
+        assert!(RawPacket::new("[99999999999999999999999]").is_err());
+
+        for invalid in ["[1,,2]", "[[1]2]", "[,]", "[12,]", "[,1]", "1,2", "[1 2]"] {
+            assert!(RawPacket::new(invalid).is_err(), "{invalid}");
+            assert!(invalid.parse::<Packet>().is_err(), "{invalid}");
+        }
+
+        for valid in ["[]", "[[],[1,[2]],3]", "7"] {
+            assert!(RawPacket::new(valid).is_ok(), "{valid}");
+        }
+    }
+
+    #[test]
+    fn display_is_canonical() -> Result<()> {
+        for line in read_input_lines("day_13")
+            .iter()
+            .filter(|line| !line.is_empty())
+        {
+            assert_eq!(line.parse::<Packet>()?.to_string(), *line);
+        }
+        assert_eq!(
+            "[ 1, [ ], [2 ,3] ]".parse::<Packet>()?.to_string(),
+            "[1,[],[2,3]]"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn trace_mixed_types() -> Result<()> {
+        let left: Packet = "[[1],[2,3,4]]".parse()?;
+        let right: Packet = "[[1],4]".parse()?;
+        let (ordering, trace) = left.cmp_with_trace(&right);
+
+        assert_eq!(ordering, Ordering::Less);
+        assert_eq!(
+            trace,
+            [
+                "- Compare [[1],[2,3,4]] vs [[1],4]",
+                "  - Compare [1] vs [1]",
+                "    - Compare 1 vs 1",
+                "  - Compare [2,3,4] vs 4",
+                "    - Mixed types; convert right to [4] and retry comparison",
+                "    - Compare [2,3,4] vs [4]",
+                "      - Compare 2 vs 4",
+                "        - Left side is smaller, so inputs are in the right order",
+            ]
+            .join("\n")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn trace_ran_out_of_items() -> Result<()> {
+        let left: Packet = "[7,7,7,7]".parse()?;
+        let right: Packet = "[7,7,7]".parse()?;
+        let (ordering, trace) = left.cmp_with_trace(&right);
+
+        assert_eq!(ordering, Ordering::Greater);
+        assert_eq!(
+            trace,
+            [
+                "- Compare [7,7,7,7] vs [7,7,7]",
+                "  - Compare 7 vs 7",
+                "  - Compare 7 vs 7",
+                "  - Compare 7 vs 7",
+                "  - Right side ran out of items, so inputs are not in the right order",
+            ]
+            .join("\n")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn trace_agrees_with_cmp() -> Result<()> {
+        for (left, right) in load_input()? {
+            assert_eq!(left.cmp_with_trace(&right).0, left.cmp(&right));
+        }
+        Ok(())
+    }
+
+    // Small ints and short lists so that equal and mixed-type comparisons come up often.
+    const GENERATOR: PacketGenerator = PacketGenerator {
+        max_depth: 4,
+        max_len: 3,
+        max_int: 3,
+    };
+
+    fn generate_packets(seed: u64, count: usize) -> Vec<Packet> {
+        let mut rng = Rng::new(seed);
+        (0..count).map(|_| GENERATOR.generate(&mut rng)).collect()
+    }
+
+    #[test]
+    fn ordering_is_reflexive() {
+        for packet in generate_packets(1, 500) {
+            assert_eq!(packet.cmp(&packet), Ordering::Equal, "{packet}");
+        }
+    }
+
+    #[test]
+    fn ordering_is_antisymmetric() {
+        let packets = generate_packets(2, 150);
+        for left in &packets {
+            for right in &packets {
+                assert_eq!(
+                    left.cmp(right),
+                    right.cmp(left).reverse(),
+                    "{left} vs {right}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn ordering_is_transitive() {
+        let packets = generate_packets(3, 40);
+        for a in &packets {
+            for b in packets.iter().filter(|b| a <= *b) {
+                for c in packets.iter().filter(|c| b <= *c) {
+                    assert!(a <= c, "{a} <= {b} <= {c}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn mixed_types_compare_as_single_item_lists() {
+        let mut rng = Rng::new(4);
+        for other in generate_packets(5, 500) {
+            let value = rng.next_u64() % 4;
+            let int = Packet::Int(value);
+            let list = Packet::List(vec![Packet::Int(value)]);
+            assert_eq!(int.cmp(&other), list.cmp(&other), "{value} vs {other}");
+            assert_eq!(other.cmp(&int), other.cmp(&list), "{other} vs {value}");
+        }
+    }
+
+    #[test]
+    fn sorting_is_stable_under_shuffling() {
+        let packets = generate_packets(6, 300);
+        let mut rng = Rng::new(7);
+
+        let mut sorted = packets.clone();
+        sorted.sort();
+        assert!(sorted.windows(2).all(|pair| pair[0] <= pair[1]));
+
+        for _ in 0..10 {
+            let mut shuffled = packets.clone();
+            rng.shuffle(&mut shuffled);
+            shuffled.sort_unstable();
+            assert_eq!(shuffled, sorted);
+        }
+    }
+
+    #[test]
+    fn generated_packets_agree_across_implementations() -> Result<()> {
+        let packets = generate_packets(8, 100);
+        let texts: Vec<String> = packets.iter().map(|packet| packet.to_string()).collect();
+
+        for (left, left_text) in zip(&packets, &texts) {
+            assert_eq!(left_text.parse::<Packet>()?.cmp(left), Ordering::Equal);
+
+            for (right, right_text) in zip(&packets, &texts) {
+                let expected = left.cmp(right);
+                let raw = RawPacket::new(left_text)?.cmp(&RawPacket::new(right_text)?);
+                assert_eq!(raw, expected, "{left} vs {right}");
+                assert_eq!(left.cmp_with_trace(right).0, expected, "{left} vs {right}");
+            }
+        }
+        Ok(())
+    }
 }
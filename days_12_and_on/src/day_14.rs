@@ -1,10 +1,17 @@
 use std::{
-    collections::HashMap,
-    fmt::Debug,
+    collections::{HashMap, HashSet},
+    fmt::{Debug, Display},
+    fs::{create_dir_all, read_to_string, write},
+    io::{stdout, Write},
     ops::{Index, IndexMut},
+    path::Path,
+    str::FromStr,
+    thread::sleep,
+    time::{Duration, Instant},
 };
 
-use anyhow::Result;
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
 
 use crate::utils::{
     grid::{Grid, Position},
@@ -12,7 +19,7 @@ use crate::utils::{
 };
 
 #[derive(Clone, Copy, PartialEq, Debug)]
-enum Tile {
+pub enum Tile {
     Air,
     Rock,
     Sand,
@@ -20,104 +27,390 @@ enum Tile {
 
 const SOURCE: (usize, usize) = (0, 500);
 
-#[derive(Default)]
-struct SandWorld {
-    pub tiles: HashMap<Position, Tile>,
-    pub has_floor: bool,
-    pub lowest_rock_row: usize,
+pub trait TileStorage: Clone {
+    // `None` is air.
+    fn get(&self, position: &Position) -> Option<Tile>;
+    fn insert(&mut self, position: Position, tile: Tile);
+    fn tiles(&self) -> Vec<(Position, Tile)>;
+    // Builds storage holding `tiles` with room for anything inside `bounds`.
+    fn build(tiles: Vec<(Position, Tile)>, bounds: (Position, Position)) -> Self;
+
+    fn contains(&self, position: &Position) -> bool {
+        self.get(position).is_some()
+    }
 }
 
-impl SandWorld {
-    fn min_col(&self) -> usize {
-        let position = self.tiles.keys().min_by(|x, y| x.col.cmp(&y.col)).unwrap();
-        position.col
+pub type SparseTiles = HashMap<Position, Tile>;
+
+impl TileStorage for SparseTiles {
+    fn get(&self, position: &Position) -> Option<Tile> {
+        HashMap::get(self, position).copied()
+    }
+
+    fn insert(&mut self, position: Position, tile: Tile) {
+        HashMap::insert(self, position, tile);
+    }
+
+    fn tiles(&self) -> Vec<(Position, Tile)> {
+        self.iter()
+            .map(|(position, tile)| (*position, *tile))
+            .collect()
+    }
+
+    fn build(tiles: Vec<(Position, Tile)>, _bounds: (Position, Position)) -> Self {
+        tiles.into_iter().collect()
+    }
+}
+
+#[derive(Clone)]
+pub struct DenseTiles {
+    grid: Grid<Tile>,
+    origin: Position,
+}
+
+impl DenseTiles {
+    fn local(&self, position: &Position) -> Option<Position> {
+        Some(Position {
+            row: position.row.checked_sub(self.origin.row)?,
+            col: position.col.checked_sub(self.origin.col)?,
+        })
     }
+}
 
-    fn max_col(&self) -> usize {
-        let position = self.tiles.keys().max_by(|x, y| x.col.cmp(&y.col)).unwrap();
-        position.col
+impl TileStorage for DenseTiles {
+    fn get(&self, position: &Position) -> Option<Tile> {
+        self.local(position)
+            .and_then(|local| self.grid.get(&local))
+            .copied()
+            .filter(|tile| *tile != Tile::Air)
     }
 
-    fn min_row(&self) -> usize {
-        let position = self.tiles.keys().min_by(|x, y| x.row.cmp(&y.row)).unwrap();
-        position.row
+    fn insert(&mut self, position: Position, tile: Tile) {
+        let cell = self
+            .local(&position)
+            .and_then(|local| self.grid.get_mut(&local))
+            .expect("Position is outside the dense storage bounds");
+        *cell = tile;
     }
 
-    fn max_row(&self) -> usize {
-        let position = self.tiles.keys().max_by(|x, y| x.row.cmp(&y.row)).unwrap();
-        position.row
+    fn tiles(&self) -> Vec<(Position, Tile)> {
+        self.grid
+            .rows()
+            .iter()
+            .enumerate()
+            .flat_map(|(row, tiles)| {
+                tiles.iter().enumerate().map(move |(col, tile)| {
+                    let position = Position::from((row + self.origin.row, col + self.origin.col));
+                    (position, *tile)
+                })
+            })
+            .filter(|(_, tile)| *tile != Tile::Air)
+            .collect()
     }
 
-    fn width(&self) -> usize {
-        self.max_col() - self.min_col()
+    fn build(tiles: Vec<(Position, Tile)>, (min, max): (Position, Position)) -> Self {
+        let mut storage = DenseTiles {
+            grid: Grid::create_and_fill(max.row - min.row + 1, max.col - min.col + 1, Tile::Air),
+            origin: min,
+        };
+
+        for (position, tile) in tiles {
+            storage.insert(position, tile);
+        }
+
+        storage
+    }
+}
+
+#[derive(Default, Clone)]
+pub struct SandWorld<S: TileStorage = SparseTiles> {
+    pub tiles: S,
+    pub config: SandConfig,
+    pub lowest_rock_row: usize,
+    pub rock_paths: Vec<RockPath>,
+    falling: Option<Position>,
+    fall_paths: Vec<Vec<Position>>,
+    next_source: usize,
+    moves: usize,
+    grains: usize,
+}
+
+fn bounding_box<'a>(positions: impl Iterator<Item = &'a Position>) -> Option<(Position, Position)> {
+    positions.fold(None, |bounds, position| {
+        let (mut min, mut max) = bounds.unwrap_or((*position, *position));
+        min.row = min.row.min(position.row);
+        min.col = min.col.min(position.col);
+        max.row = max.row.max(position.row);
+        max.col = max.col.max(position.col);
+        Some((min, max))
+    })
+}
+
+impl<S: TileStorage> SandWorld<S> {
+    // Everywhere sand could ever settle: below a source, sand moves at most `spread` columns
+    // sideways per row it falls, and it can't settle below the floor or the lowest rock.
+    fn storage_bounds(&self) -> (Position, Position) {
+        let tiles = self.tiles.tiles();
+        let (mut min, mut max) = bounding_box(
+            tiles
+                .iter()
+                .map(|(position, _)| position)
+                .chain(self.config.sources.iter()),
+        )
+        .unwrap_or_default();
+
+        let last_row = match self.floor_row() {
+            Some(floor_row) => floor_row,
+            None => self.lowest_rock_row + 1,
+        };
+        let spread = self
+            .config
+            .moves
+            .iter()
+            .map(|possible_move| possible_move.1.unsigned_abs() as usize)
+            .max()
+            .unwrap_or(0);
+
+        for source in self.config.sources.iter() {
+            let reach = spread * last_row.saturating_sub(source.row);
+            min.col = min.col.min(source.col.saturating_sub(reach));
+            max.col = max.col.max(source.col + reach);
+        }
+
+        max.row = max.row.max(last_row);
+        (min, max)
     }
 
-    fn height(&self) -> usize {
-        self.max_row() - self.min_row()
+    // Moves the world onto another storage backend, e.g. `with_storage::<DenseTiles>()`.
+    pub fn with_storage<T: TileStorage>(self) -> SandWorld<T> {
+        let tiles = T::build(self.tiles.tiles(), self.storage_bounds());
+
+        SandWorld {
+            tiles,
+            config: self.config,
+            lowest_rock_row: self.lowest_rock_row,
+            rock_paths: self.rock_paths,
+            falling: self.falling,
+            fall_paths: self.fall_paths,
+            next_source: self.next_source,
+            moves: self.moves,
+            grains: self.grains,
+        }
     }
 }
 
-impl From<&SandWorld> for Grid<Tile> {
-    fn from(value: &SandWorld) -> Self {
-        let mut grid = Grid::create_and_fill(value.height() + 1, value.width() + 1, Tile::Air);
-        let min_row = value.min_row();
-        let min_col = value.min_col();
+impl<S: TileStorage> From<&SandWorld<S>> for Grid<Tile> {
+    fn from(value: &SandWorld<S>) -> Self {
+        let tiles = value.tiles.tiles();
+        let (min, max) =
+            bounding_box(tiles.iter().map(|(position, _)| position)).unwrap_or_default();
+        let mut grid =
+            Grid::create_and_fill(max.row - min.row + 1, max.col - min.col + 1, Tile::Air);
 
-        value.tiles.iter().for_each(|(position, tile)| {
-            grid[position.row - min_row][position.col - min_col] = *tile
+        tiles.iter().for_each(|(position, tile)| {
+            grid[position.row - min.row][position.col - min.col] = *tile
         });
 
         grid
     }
 }
 
-fn load_input(file_name: &str) -> SandWorld {
-    let lines = read_input_lines(file_name);
-    let paths: Vec<Vec<(usize, usize)>> = lines
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct RockPath(pub Vec<Position>);
+
+// A horizontal or vertical run of rock, with `start` above or left of `end`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Segment {
+    pub start: Position,
+    pub end: Position,
+}
+
+fn parse_point(token: &str) -> Result<Position> {
+    let (col, row) = token
+        .trim()
+        .split_once(',')
+        .ok_or_else(|| anyhow!("Expected a point like `x,y`, found {token:?}"))?;
+
+    let col: usize = col
+        .parse()
+        .with_context(|| format!("Invalid x coordinate {col:?}"))?;
+    let row: usize = row
+        .parse()
+        .with_context(|| format!("Invalid y coordinate {row:?}"))?;
+
+    Ok(Position { row, col })
+}
+
+impl FromStr for RockPath {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let points = s
+            .split(" -> ")
+            .map(parse_point)
+            .collect::<Result<Vec<Position>>>()?;
+
+        for window in points.windows(2) {
+            let (start, end) = (window[0], window[1]);
+
+            if start.row != end.row && start.col != end.col {
+                return Err(anyhow!(
+                    "Diagonal segment from {},{} to {},{}",
+                    start.col,
+                    start.row,
+                    end.col,
+                    end.row
+                ));
+            }
+        }
+
+        Ok(RockPath(points))
+    }
+}
+
+impl Display for RockPath {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let points: Vec<String> = self
+            .0
+            .iter()
+            .map(|point| format!("{},{}", point.col, point.row))
+            .collect();
+
+        f.write_str(&points.join(" -> "))
+    }
+}
+
+impl TryFrom<String> for RockPath {
+    type Error = anyhow::Error;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<RockPath> for String {
+    fn from(value: RockPath) -> Self {
+        value.to_string()
+    }
+}
+
+impl From<Segment> for RockPath {
+    fn from(value: Segment) -> Self {
+        RockPath(vec![value.start, value.end])
+    }
+}
+
+impl RockPath {
+    fn segments(&self) -> impl Iterator<Item = Segment> + '_ {
+        let single_point = (self.0.len() == 1).then(|| (self.0[0], self.0[0]));
+
+        self.0
+            .windows(2)
+            .map(|window| (window[0], window[1]))
+            .chain(single_point)
+            .map(|(start, end)| Segment {
+                start: Position::from((start.row.min(end.row), start.col.min(end.col))),
+                end: Position::from((start.row.max(end.row), start.col.max(end.col))),
+            })
+    }
+}
+
+pub fn parse_rock_paths(lines: &[String]) -> Result<Vec<RockPath>> {
+    lines
         .iter()
-        .map(|line| {
-            line.split(" -> ")
-                .map(|token| {
-                    let num_tokens: Vec<&str> = token.split(',').collect();
-                    let col: usize = num_tokens.first().unwrap().parse().unwrap();
-                    let row: usize = num_tokens.last().unwrap().parse().unwrap();
-                    (row, col)
-                })
-                .collect()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| {
+            line.parse::<RockPath>()
+                .with_context(|| format!("Invalid rock path on line {}", index + 1))
         })
-        .collect();
+        .collect()
+}
 
-    let rows = paths.iter().flatten().map(|coord| coord.0);
-    let max_row = rows.max().unwrap();
+// Every segment of every path, with overlapping or touching segments along the same row or
+// column merged into one. Real inputs repeat the same paths many times over.
+pub fn dedupe_segments(paths: &[RockPath]) -> Vec<Segment> {
+    let mut segments: Vec<Segment> = paths.iter().flat_map(RockPath::segments).collect();
+    segments.sort_by_key(|segment| {
+        let is_vertical =
+            segment.start.col == segment.end.col && segment.start.row != segment.end.row;
+        if is_vertical {
+            (1, segment.start.col, segment.start.row, segment.end.row)
+        } else {
+            (0, segment.start.row, segment.start.col, segment.end.col)
+        }
+    });
 
-    let mut world = SandWorld {
-        tiles: HashMap::new(),
-        lowest_rock_row: max_row,
-        has_floor: false,
-    };
+    let mut merged: Vec<Segment> = vec![];
 
-    paths.iter().for_each(|path| {
-        path.windows(2).for_each(|window| {
-            let (start, end) = (window.first().unwrap(), window.last().unwrap());
+    for segment in segments {
+        if let Some(last) = merged.last_mut() {
+            let same_row = last.start.row == last.end.row
+                && segment.start.row == segment.end.row
+                && last.start.row == segment.start.row;
+            let same_col = last.start.col == last.end.col
+                && segment.start.col == segment.end.col
+                && last.start.col == segment.start.col;
 
-            let mut row_range = [start.0, end.0];
-            row_range.sort();
-            let row_range = row_range[0]..(row_range[1] + 1);
+            if same_row && segment.start.col <= last.end.col + 1 {
+                last.end.col = last.end.col.max(segment.end.col);
+                continue;
+            }
 
-            let mut col_range = [start.1, end.1];
-            col_range.sort();
-            let col_range = col_range[0]..(col_range[1] + 1);
+            if same_col && !same_row && segment.start.row <= last.end.row + 1 {
+                last.end.row = last.end.row.max(segment.end.row);
+                continue;
+            }
+        }
 
-            for row in row_range {
-                for col in col_range.clone() {
+        merged.push(segment);
+    }
+
+    merged
+}
+
+pub fn load_input(file_name: &str) -> Result<SandWorld> {
+    SandWorld::from_lines(&read_input_lines(file_name))
+}
+
+impl SandWorld {
+    pub fn from_lines(lines: &[String]) -> Result<SandWorld> {
+        SandWorld::from_rock_paths(parse_rock_paths(lines)?)
+    }
+
+    pub fn from_rock_paths(rock_paths: Vec<RockPath>) -> Result<SandWorld> {
+        let max_row = rock_paths
+            .iter()
+            .flat_map(|path| path.0.iter())
+            .map(|point| point.row)
+            .max()
+            .ok_or_else(|| anyhow!("There are no rock paths"))?;
+
+        let mut world = SandWorld {
+            tiles: HashMap::new(),
+            lowest_rock_row: max_row,
+            config: SandConfig::default(),
+            rock_paths: vec![],
+            falling: None,
+            fall_paths: vec![],
+            next_source: 0,
+            moves: 0,
+            grains: 0,
+        };
+
+        for segment in dedupe_segments(&rock_paths) {
+            for row in segment.start.row..=segment.end.row {
+                for col in segment.start.col..=segment.end.col {
                     let position = Position { row, col };
                     world.tiles.insert(position, Tile::Rock);
                 }
             }
-        });
-    });
+        }
 
-    world
+        world.rock_paths = rock_paths;
+        Ok(world)
+    }
 }
 
 impl Debug for Grid<Tile> {
@@ -142,45 +435,641 @@ impl Debug for Grid<Tile> {
 }
 
 const POSSIBLE_MOVES: [(i32, i32); 3] = [(1, 0), (1, -1), (1, 1)];
+const FLOOR_OFFSET: usize = 2;
 
-impl SandWorld {
-    fn drop_sand(&mut self) -> bool {
-        let tiles = &mut self.tiles;
-        let mut current_position: Position = Position::from(SOURCE);
-
-        'a: while current_position.row < self.lowest_rock_row + 1 {
-            for possible_move in POSSIBLE_MOVES {
-                let possible_position = Position::from((
-                    ((current_position.row as i32) + possible_move.0) as usize,
-                    ((current_position.col as i32) + possible_move.1) as usize,
-                ));
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SandConfig {
+    pub sources: Vec<Position>,
+    pub moves: Vec<(i32, i32)>,
+    // Rows below the lowest rock that the floor sits at, or `None` for a bottomless cave.
+    pub floor_offset: Option<usize>,
+}
+
+impl Default for SandConfig {
+    fn default() -> Self {
+        Self {
+            sources: vec![Position::from(SOURCE)],
+            moves: POSSIBLE_MOVES.to_vec(),
+            floor_offset: None,
+        }
+    }
+}
+
+impl SandConfig {
+    pub fn with_floor() -> Self {
+        Self {
+            floor_offset: Some(FLOOR_OFFSET),
+            ..Self::default()
+        }
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.sources.is_empty() {
+            return Err(anyhow!("At least one source is required"));
+        }
+
+        if self.moves.is_empty() {
+            return Err(anyhow!("At least one move is required"));
+        }
+
+        if let Some(possible_move) = self.moves.iter().find(|possible_move| possible_move.0 < 1) {
+            return Err(anyhow!(
+                "Move {possible_move:?} doesn't go down, so sand could fall forever"
+            ));
+        }
+
+        if self.floor_offset == Some(0) {
+            return Err(anyhow!("The floor must be below the lowest rock"));
+        }
+
+        Ok(())
+    }
+}
 
-                if tiles.get(&possible_position).is_none() {
-                    current_position = possible_position;
-                    continue 'a;
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SandEvent {
+    Spawned(Position),
+    Moved(Position),
+    CameToRest(Position),
+    FellIntoAbyss(Position),
+    SourceBlocked,
+}
+
+impl<S: TileStorage> SandWorld<S> {
+    pub fn with_config(mut self, config: SandConfig) -> Result<Self> {
+        config.validate()?;
+        self.config = config;
+        self.fall_paths.clear();
+        self.next_source = 0;
+        self.tiles = S::build(self.tiles.tiles(), self.storage_bounds());
+        Ok(self)
+    }
+
+    fn floor_row(&self) -> Option<usize> {
+        self.config
+            .floor_offset
+            .map(|offset| self.lowest_rock_row + offset)
+    }
+
+    fn is_blocked(&self, position: &Position) -> bool {
+        self.tiles.contains(position)
+            || self
+                .floor_row()
+                .is_some_and(|floor_row| position.row >= floor_row)
+    }
+
+    fn next_position(&self, current_position: &Position) -> Option<Position> {
+        self.config
+            .moves
+            .iter()
+            .filter_map(|possible_move| {
+                let row = (current_position.row as i64) + possible_move.0 as i64;
+                let col = (current_position.col as i64) + possible_move.1 as i64;
+                Some(Position::from((row.try_into().ok()?, col.try_into().ok()?)))
+            })
+            .find(|possible_position| !self.is_blocked(possible_position))
+    }
+
+    // Sources take turns, skipping any that are already covered.
+    fn next_source(&mut self) -> Option<(usize, Position)> {
+        let count = self.config.sources.len();
+
+        let (index, source) = (0..count)
+            .map(|offset| (self.next_source + offset) % count)
+            .map(|index| (index, self.config.sources[index]))
+            .find(|(_, source)| !self.is_blocked(source))?;
+
+        self.next_source = (index + 1) % count;
+        Some((index, source))
+    }
+
+    fn is_in_abyss(&self, position: &Position) -> bool {
+        self.floor_row().is_none() && position.row > self.lowest_rock_row
+    }
+
+    // Any remembered fall path through the new sand is only valid up to the cell above it.
+    fn settle(&mut self, position: Position) {
+        self.tiles.insert(position, Tile::Sand);
+        self.grains += 1;
+
+        for fall_path in self.fall_paths.iter_mut() {
+            if let Some(index) = fall_path.iter().rposition(|other| *other == position) {
+                fall_path.truncate(index);
+            }
+        }
+    }
+
+    // Total number of single-cell moves made by grains so far.
+    pub fn moves(&self) -> usize {
+        self.moves
+    }
+
+    // Number of grains that have come to rest so far.
+    pub fn grains(&self) -> usize {
+        self.grains
+    }
+
+    // Advances the simulation by one tick: spawns a grain at the next source if none is
+    // falling, otherwise moves the falling grain one cell or settles it.
+    pub fn step(&mut self) -> SandEvent {
+        let Some(current_position) = self.falling else {
+            let Some((_, source)) = self.next_source() else {
+                return SandEvent::SourceBlocked;
+            };
+
+            self.falling = Some(source);
+            return SandEvent::Spawned(source);
+        };
+
+        if self.is_in_abyss(&current_position) {
+            self.falling = None;
+            return SandEvent::FellIntoAbyss(current_position);
+        }
+
+        if let Some(possible_position) = self.next_position(&current_position) {
+            self.falling = Some(possible_position);
+            self.moves += 1;
+            return SandEvent::Moved(possible_position);
+        }
+
+        self.falling = None;
+        self.settle(current_position);
+        SandEvent::CameToRest(current_position)
+    }
+
+    // Runs ticks until the simulation can't make progress: the first grain that falls into the
+    // abyss or every source being blocked is the last event.
+    pub fn events(&mut self) -> SandEvents<'_, S> {
+        SandEvents {
+            world: self,
+            done: false,
+        }
+    }
+
+    // Drops a single grain all the way. Returns whether it came to rest.
+    //
+    // Only the cell a grain settles in changes, so the next grain from the same source follows
+    // the previous grain's path up to the cell before that one. Each source's path is kept as a
+    // stack and grains resume from the top of it instead of from the source.
+    pub fn drop_sand(&mut self) -> bool {
+        if self.falling.is_some() {
+            return self.finish_falling_grain();
+        }
+
+        let Some((index, source)) = self.next_source() else {
+            return false;
+        };
+
+        self.fall_paths
+            .resize(self.config.sources.len(), Vec::new());
+
+        if self.fall_paths[index].is_empty() {
+            self.fall_paths[index].push(source);
+        }
+
+        loop {
+            let current_position = *self.fall_paths[index].last().unwrap();
+
+            if self.is_in_abyss(&current_position) {
+                return false;
+            }
+
+            match self.next_position(&current_position) {
+                Some(possible_position) => {
+                    self.fall_paths[index].push(possible_position);
+                    self.moves += 1;
+                }
+                None => {
+                    self.settle(current_position);
+                    return true;
+                }
+            }
+        }
+    }
+
+    fn finish_falling_grain(&mut self) -> bool {
+        loop {
+            match self.step() {
+                SandEvent::Spawned(_) | SandEvent::Moved(_) => {}
+                SandEvent::CameToRest(_) => return true,
+                SandEvent::FellIntoAbyss(_) | SandEvent::SourceBlocked => return false,
+            }
+        }
+    }
+
+    // Drops grains until one falls into the abyss or every source is blocked, and returns how
+    // many came to rest.
+    pub fn fill_until_blocked(&mut self) -> usize {
+        let mut count: usize = 0;
+
+        while self.drop_sand() {
+            count += 1;
+        }
+
+        count
+    }
+}
+
+pub struct SandEvents<'a, S: TileStorage> {
+    world: &'a mut SandWorld<S>,
+    done: bool,
+}
+
+impl<'a, S: TileStorage> Iterator for SandEvents<'a, S> {
+    type Item = SandEvent;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let event = self.world.step();
+        self.done = matches!(
+            event,
+            SandEvent::FellIntoAbyss(_) | SandEvent::SourceBlocked
+        );
+        Some(event)
+    }
+}
+
+impl<S: TileStorage> SandWorld<S> {
+    // Number of grains a fill of the empty cave ends with when there is a floor, without
+    // simulating it. A cell fills exactly when it isn't rock and some cell a move away above it
+    // fills, so the pile is every cell reachable from a source minus everything rock shadows.
+    pub fn floor_fill_count(&self) -> Option<usize> {
+        let floor_row = self.floor_row()?;
+        let first_row = self.config.sources.iter().map(|source| source.row).min()?;
+        let is_rock = |row: usize, col: i64| {
+            col < 0 || self.tiles.get(&Position::from((row, col as usize))) == Some(Tile::Rock)
+        };
+
+        let mut reached: Vec<HashSet<i64>> = vec![];
+        let mut count: usize = 0;
+
+        for row in first_row..floor_row {
+            let mut cols: HashSet<i64> = self
+                .config
+                .sources
+                .iter()
+                .filter(|source| source.row == row)
+                .map(|source| source.col as i64)
+                .collect();
+
+            for possible_move in self.config.moves.iter() {
+                let rows_up = possible_move.0 as usize;
+
+                if let Some(above) = reached.len().checked_sub(rows_up).map(|i| &reached[i]) {
+                    cols.extend(above.iter().map(|col| col + possible_move.1 as i64));
                 }
             }
 
-            tiles.insert(current_position, Tile::Sand);
-            return true;
+            cols.retain(|col| !is_rock(row, *col));
+            count += cols.len();
+            reached.push(cols);
         }
 
-        if self.has_floor {
-            tiles.insert(current_position, Tile::Sand);
+        Some(count)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct AnimationOptions {
+    pub frames_per_second: f64,
+    pub grains_per_frame: usize,
+    pub colour: bool,
+}
+
+const RESET_COLOUR: &str = "\x1b[0m";
+
+impl<S: TileStorage> SandWorld<S> {
+    // Smallest box holding every tile, the sources, the falling grain and the floor if there is
+    // one.
+    fn viewport(&self) -> (Position, Position) {
+        let mut min = Position::from((usize::MAX, usize::MAX));
+        let mut max = Position::from((0, 0));
+
+        let tiles = self.tiles.tiles();
+
+        for position in tiles
+            .iter()
+            .map(|(position, _)| position)
+            .chain(self.config.sources.iter())
+            .chain(self.falling.iter())
+        {
+            min.row = min.row.min(position.row);
+            min.col = min.col.min(position.col);
+            max.row = max.row.max(position.row);
+            max.col = max.col.max(position.col);
+        }
+
+        if let Some(floor_row) = self.floor_row() {
+            max.row = max.row.max(floor_row);
         }
 
-        false
+        (min, max)
+    }
+
+    // What a picture of the cave shows at `position`, including the falling grain and the floor.
+    fn visible_tile(&self, position: &Position) -> Tile {
+        match self.tiles.get(position) {
+            Some(tile) => tile,
+            None if self.falling == Some(*position) => Tile::Sand,
+            None if self.floor_row() == Some(position.row) => Tile::Rock,
+            None => Tile::Air,
+        }
+    }
+
+    fn crop(&self, (min, max): (Position, Position)) -> Grid<Tile> {
+        let tiles: Vec<Tile> = (min.row..=max.row)
+            .flat_map(|row| (min.col..=max.col).map(move |col| Position { row, col }))
+            .map(|position| self.visible_tile(&position))
+            .collect();
+
+        Grid::new(tiles, max.col - min.col + 1)
+    }
+
+    pub fn render_frame(&self, colour: bool) -> String {
+        let (min, max) = self.viewport();
+        (min.row..=max.row)
+            .map(|row| {
+                let mut line: String = (min.col..=max.col)
+                    .map(|col| {
+                        let position = Position { row, col };
+
+                        let (symbol, code) = match self.visible_tile(&position) {
+                            Tile::Air if self.config.sources.contains(&position) => {
+                                ('+', "\x1b[31m")
+                            }
+                            Tile::Air => ('.', "\x1b[2m"),
+                            Tile::Rock => ('#', "\x1b[90m"),
+                            Tile::Sand => ('o', "\x1b[33m"),
+                        };
+
+                        if colour {
+                            format!("{code}{symbol}{RESET_COLOUR}")
+                        } else {
+                            symbol.to_string()
+                        }
+                    })
+                    .collect();
+
+                line.push('\n');
+                line
+            })
+            .collect()
     }
 }
 
+// Redraws the cropped cave in place, dropping `grains_per_frame` grains between frames, until
+// no more sand can come to rest.
+pub fn animate<S: TileStorage>(world: &mut SandWorld<S>, options: AnimationOptions) -> Result<()> {
+    let fps = options.frames_per_second;
+    if fps.is_nan() || fps <= 0.0 {
+        return Err(anyhow!("Frames per second must be positive, got {fps}"));
+    }
+    let frame_duration = Duration::try_from_secs_f64(1.0 / fps)
+        .map_err(|_| anyhow!("Frames per second {fps} is too small"))?;
+
+    let mut out = stdout().lock();
+    let mut count: usize = 0;
+
+    write!(out, "\x1b[?25l\x1b[2J")?;
+
+    loop {
+        write!(out, "\x1b[H\x1b[J{}", world.render_frame(options.colour))?;
+        writeln!(out, "Grains at rest: {count}")?;
+        out.flush()?;
+
+        let mut settled = true;
+        for _ in 0..options.grains_per_frame.max(1) {
+            settled = world.drop_sand();
+            if !settled {
+                break;
+            }
+            count += 1;
+        }
+
+        if !settled {
+            break;
+        }
+
+        sleep(frame_duration);
+    }
+
+    write!(out, "\x1b[?25h")?;
+    out.flush()?;
+    Ok(())
+}
+
+impl Tile {
+    fn rgb(&self) -> [u8; 3] {
+        match self {
+            Tile::Air => [24, 24, 36],
+            Tile::Rock => [110, 110, 110],
+            Tile::Sand => [232, 192, 96],
+        }
+    }
+}
+
+impl Grid<Tile> {
+    // Binary PPM (P6), with every tile drawn as a `scale` x `scale` square.
+    pub fn to_ppm(&self, scale: usize) -> Vec<u8> {
+        let scale = scale.max(1);
+        let header = format!(
+            "P6\n{} {}\n255\n",
+            self.width() * scale,
+            self.height() * scale
+        );
+        let mut bytes = header.into_bytes();
+
+        for row in self.rows() {
+            let line: Vec<u8> = row
+                .iter()
+                .flat_map(|tile| tile.rgb().repeat(scale))
+                .collect();
+
+            for _ in 0..scale {
+                bytes.extend_from_slice(&line);
+            }
+        }
+
+        bytes
+    }
+}
+
+impl<S: TileStorage> SandWorld<S> {
+    pub fn write_ppm(&self, path: &Path, scale: usize) -> Result<()> {
+        write(path, self.crop(self.viewport()).to_ppm(scale))?;
+        Ok(())
+    }
+}
+
+// Writes `frame_00000.ppm`, `frame_00001.ppm`, ... to `directory`: one frame before any sand
+// falls, then one every `grains_per_frame` grains, then the final state. Every frame is cropped
+// to the final viewport so they can be stitched into a timelapse. Returns the number of frames.
+pub fn export_frames<S: TileStorage>(
+    world: &mut SandWorld<S>,
+    directory: &Path,
+    grains_per_frame: usize,
+    scale: usize,
+) -> Result<usize> {
+    let mut finished = world.clone();
+    while finished.drop_sand() {}
+    let viewport = finished.viewport();
+
+    create_dir_all(directory)?;
+
+    let mut frames: usize = 0;
+    let mut write_frame = |world: &SandWorld<S>| -> Result<()> {
+        let path = directory.join(format!("frame_{frames:05}.ppm"));
+        write(path, world.crop(viewport).to_ppm(scale))?;
+        frames += 1;
+        Ok(())
+    };
+
+    write_frame(world)?;
+
+    let grains_per_frame = grains_per_frame.max(1);
+    let mut count: usize = 0;
+    while world.drop_sand() {
+        count += 1;
+        if count.is_multiple_of(grains_per_frame) {
+            write_frame(world)?;
+        }
+    }
+
+    if !count.is_multiple_of(grains_per_frame) {
+        write_frame(world)?;
+    }
+
+    Ok(frames)
+}
+
+// Everything needed to rebuild a `SandWorld` mid-simulation. Rock is kept as the original
+// paths rather than every rock tile, and the remembered fall paths are rebuilt on demand.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub config: SandConfig,
+    pub rock_paths: Vec<RockPath>,
+    pub sand: Vec<Position>,
+    pub falling: Option<Position>,
+    // Which source spawns the next grain when there are several.
+    pub next_source: usize,
+    pub grains: usize,
+    pub moves: usize,
+}
+
+impl Snapshot {
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(self)?)
+    }
+
+    pub fn from_json(json: &str) -> Result<Self> {
+        Ok(serde_json::from_str(json)?)
+    }
+}
+
+impl<S: TileStorage> SandWorld<S> {
+    pub fn snapshot(&self) -> Snapshot {
+        let mut sand: Vec<Position> = self
+            .tiles
+            .tiles()
+            .into_iter()
+            .filter(|(_, tile)| *tile == Tile::Sand)
+            .map(|(position, _)| position)
+            .collect();
+        sand.sort_by_key(|position| (position.row, position.col));
+
+        Snapshot {
+            config: self.config.clone(),
+            rock_paths: self.rock_paths.clone(),
+            sand,
+            falling: self.falling,
+            next_source: self.next_source,
+            grains: self.grains,
+            moves: self.moves,
+        }
+    }
+
+    pub fn save_snapshot(&self, path: &Path) -> Result<()> {
+        write(path, self.snapshot().to_json()?)?;
+        Ok(())
+    }
+}
+
+impl SandWorld {
+    pub fn from_snapshot(snapshot: Snapshot) -> Result<SandWorld> {
+        let mut world =
+            SandWorld::from_rock_paths(snapshot.rock_paths)?.with_config(snapshot.config)?;
+
+        for position in snapshot.sand {
+            if world.tiles.contains(&position) {
+                return Err(anyhow!(
+                    "Sand at {},{} overlaps rock",
+                    position.col,
+                    position.row
+                ));
+            }
+
+            world.tiles.insert(position, Tile::Sand);
+        }
+
+        if snapshot.next_source >= world.config.sources.len() {
+            return Err(anyhow!(
+                "Next source {} is out of range for {} sources",
+                snapshot.next_source,
+                world.config.sources.len()
+            ));
+        }
+
+        world.falling = snapshot.falling;
+        world.next_source = snapshot.next_source;
+        world.grains = snapshot.grains;
+        world.moves = snapshot.moves;
+        Ok(world)
+    }
+
+    pub fn load_snapshot(path: &Path) -> Result<SandWorld> {
+        let json = read_to_string(path)
+            .with_context(|| format!("Couldn't read snapshot {}", path.display()))?;
+        SandWorld::from_snapshot(Snapshot::from_json(&json)?)
+    }
+}
+
+fn bench_fill<S: TileStorage>(name: &str, world: SandWorld<S>) -> Result<()> {
+    for config in [SandConfig::default(), SandConfig::with_floor()] {
+        let mut world = world.clone().with_config(config.clone())?;
+
+        let now = Instant::now();
+        let count = world.fill_until_blocked();
+        let elapsed = now.elapsed();
+
+        let floor = if config.floor_offset.is_some() {
+            "floor"
+        } else {
+            "abyss"
+        };
+        println!("{name} ({floor}): {count} grains in {elapsed:?}");
+    }
+
+    Ok(())
+}
+
+pub fn bench_day_14(file_name: &str) -> Result<()> {
+    let world = load_input(file_name)?;
+    bench_fill("HashMap", world.clone())?;
+    bench_fill("Grid", world.with_storage::<DenseTiles>())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::utils::rng::Rng;
     use anyhow::{Ok, Result};
 
     #[test]
     fn example() -> Result<()> {
-        let mut sand_grid = load_input("day_14_example");
+        let mut sand_grid = load_input("day_14_example")?;
 
         let mut count: i32 = 0;
 
@@ -194,7 +1083,7 @@ mod tests {
 
     #[test]
     fn part_1() -> Result<()> {
-        let mut sand_world = load_input("day_14");
+        let mut sand_world = load_input("day_14")?;
 
         let mut count: i32 = 0;
 
@@ -208,8 +1097,7 @@ mod tests {
 
     #[test]
     fn example_part_2() -> Result<()> {
-        let mut sand_world = load_input("day_14_example");
-        sand_world.has_floor = true;
+        let mut sand_world = load_input("day_14_example")?.with_config(SandConfig::with_floor())?;
 
         let mut count: i32 = 0;
 
@@ -222,25 +1110,553 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn animate_rejects_invalid_fps() -> Result<()> {
+        let mut world = load_input("day_14_example")?;
+
+        for fps in [0.0, -1.0, f64::NAN, 1e-320] {
+            let options = AnimationOptions {
+                frames_per_second: fps,
+                grains_per_frame: 1,
+                colour: false,
+            };
+            assert!(animate(&mut world, options).is_err());
+        }
+
+        assert_eq!(world.grains(), 0);
+        Ok(())
+    }
+
     #[test]
     fn part_2() -> Result<()> {
-        let mut sand_world = load_input("day_14");
-        sand_world.has_floor = true;
+        let mut sand_world = load_input("day_14")?.with_config(SandConfig::with_floor())?;
 
         let mut count: i32 = 0;
 
         while sand_world.tiles.get(&Position::from(SOURCE)) != Some(&Tile::Sand) {
             sand_world.drop_sand();
             count += 1;
-
-            // if count % 100 == 0 {
-            //     let grid: Grid<Tile> = (&sand_world).try_into().unwrap();
-            //     println!("{:?}", grid);
-            //     println!();
-            // }
         }
 
         assert_eq!(count, 26729);
         Ok(())
     }
+
+    #[test]
+    fn first_grain_events() -> Result<()> {
+        let mut sand_world = load_input("day_14_example")?;
+
+        let events: Vec<SandEvent> = sand_world.events().take(10).collect();
+
+        assert_eq!(
+            events,
+            vec![
+                SandEvent::Spawned(Position::from((0, 500))),
+                SandEvent::Moved(Position::from((1, 500))),
+                SandEvent::Moved(Position::from((2, 500))),
+                SandEvent::Moved(Position::from((3, 500))),
+                SandEvent::Moved(Position::from((4, 500))),
+                SandEvent::Moved(Position::from((5, 500))),
+                SandEvent::Moved(Position::from((6, 500))),
+                SandEvent::Moved(Position::from((7, 500))),
+                SandEvent::Moved(Position::from((8, 500))),
+                SandEvent::CameToRest(Position::from((8, 500))),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn grains_slide_left_then_right() -> Result<()> {
+        let mut sand_world = load_input("day_14_example")?;
+
+        let resting: Vec<Position> = sand_world
+            .events()
+            .filter_map(|event| match event {
+                SandEvent::CameToRest(position) => Some(position),
+                _ => None,
+            })
+            .take(3)
+            .collect();
+
+        assert_eq!(
+            resting,
+            vec![
+                Position::from((8, 500)),
+                Position::from((8, 499)),
+                Position::from((8, 501)),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn example_events_end_in_abyss() -> Result<()> {
+        let mut sand_world = load_input("day_14_example")?;
+
+        let events: Vec<SandEvent> = sand_world.events().collect();
+        let resting = events
+            .iter()
+            .filter(|event| matches!(event, SandEvent::CameToRest(_)))
+            .count();
+
+        assert_eq!(resting, 24);
+        assert!(matches!(events.last(), Some(SandEvent::FellIntoAbyss(_))));
+        Ok(())
+    }
+
+    #[test]
+    fn example_part_2_events_end_with_blocked_source() -> Result<()> {
+        let mut sand_world = load_input("day_14_example")?.with_config(SandConfig::with_floor())?;
+
+        let events: Vec<SandEvent> = sand_world.events().collect();
+        let spawned = events
+            .iter()
+            .filter(|event| matches!(event, SandEvent::Spawned(_)))
+            .count();
+
+        assert_eq!(spawned, 93);
+        assert_eq!(events.last(), Some(&SandEvent::SourceBlocked));
+        assert_eq!(
+            events[events.len() - 2],
+            SandEvent::CameToRest(Position::from(SOURCE))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn render_example_frame() -> Result<()> {
+        let mut sand_world = load_input("day_14_example")?;
+        while sand_world.drop_sand() {}
+
+        assert_eq!(
+            sand_world.render_frame(false),
+            [
+                "......+...",
+                "..........",
+                "......o...",
+                ".....ooo..",
+                "....#ooo##",
+                "...o#ooo#.",
+                "..###ooo#.",
+                "....oooo#.",
+                ".o.ooooo#.",
+                "#########.",
+                "",
+            ]
+            .join("\n")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn render_frame_includes_floor() -> Result<()> {
+        let mut sand_world = load_input("day_14_example")?.with_config(SandConfig::with_floor())?;
+        while sand_world.drop_sand() {}
+
+        let frame = sand_world.render_frame(false);
+        let last_row = frame.lines().last().unwrap();
+
+        assert_eq!(frame.lines().count(), 12);
+        assert!(last_row.chars().all(|c| c == '#'));
+        assert!(frame.lines().next().unwrap().contains('o'));
+        Ok(())
+    }
+
+    #[test]
+    fn example_ppm() -> Result<()> {
+        let mut sand_world = load_input("day_14_example")?;
+        while sand_world.drop_sand() {}
+
+        let grid = sand_world.crop(sand_world.viewport());
+        let ppm = grid.to_ppm(2);
+        let header = b"P6\n20 20\n255\n";
+
+        assert_eq!(&ppm[..header.len()], header);
+        assert_eq!(ppm.len(), header.len() + 20 * 20 * 3);
+
+        // Bottom-left pixel is the rock at 494,9.
+        let last_row = &ppm[ppm.len() - 20 * 3..];
+        assert_eq!(last_row[..3], Tile::Rock.rgb());
+        Ok(())
+    }
+
+    #[test]
+    fn example_frames() -> Result<()> {
+        let directory = std::env::temp_dir().join("day_14_example_frames");
+        let mut sand_world = load_input("day_14_example")?;
+
+        let frames = export_frames(&mut sand_world, &directory, 5, 1)?;
+
+        // Before any sand, after 5, 10, 15 and 20 grains, and the final 24.
+        assert_eq!(frames, 6);
+        let first = std::fs::read(directory.join("frame_00000.ppm"))?;
+        let last = std::fs::read(directory.join("frame_00005.ppm"))?;
+        assert_eq!(first.len(), last.len());
+        assert_ne!(first, last);
+
+        std::fs::remove_dir_all(directory)?;
+        Ok(())
+    }
+
+    #[test]
+    fn fill_until_blocked() -> Result<()> {
+        let mut sand_world = load_input("day_14")?;
+        assert_eq!(sand_world.fill_until_blocked(), 825);
+
+        let mut sand_world = load_input("day_14")?.with_config(SandConfig::with_floor())?;
+        assert_eq!(sand_world.fill_until_blocked(), 26729);
+        Ok(())
+    }
+
+    #[test]
+    fn memoised_drop_matches_stepping() -> Result<()> {
+        let mut stepped = load_input("day_14")?.with_config(SandConfig::with_floor())?;
+        let stepped_count = stepped
+            .events()
+            .filter(|event| matches!(event, SandEvent::CameToRest(_)))
+            .count();
+
+        let mut memoised = load_input("day_14")?.with_config(SandConfig::with_floor())?;
+        let memoised_count = memoised.fill_until_blocked();
+
+        assert_eq!(memoised_count, stepped_count);
+        assert_eq!(memoised.tiles, stepped.tiles);
+        assert!(memoised.moves() * 10 < stepped.moves());
+        Ok(())
+    }
+
+    #[test]
+    fn stepping_after_memoised_drops() -> Result<()> {
+        let mut sand_world = load_input("day_14_example")?.with_config(SandConfig::with_floor())?;
+
+        for _ in 0..10 {
+            sand_world.drop_sand();
+        }
+        while sand_world.step() != SandEvent::Moved(Position::from((2, 500))) {}
+
+        assert_eq!(sand_world.fill_until_blocked(), 83);
+        Ok(())
+    }
+
+    #[test]
+    fn floor_fill_count() -> Result<()> {
+        let sand_world = load_input("day_14_example")?.with_config(SandConfig::with_floor())?;
+        assert_eq!(sand_world.floor_fill_count(), Some(93));
+
+        let sand_world = load_input("day_14")?.with_config(SandConfig::with_floor())?;
+        assert_eq!(sand_world.floor_fill_count(), Some(26729));
+
+        assert_eq!(load_input("day_14")?.floor_fill_count(), None);
+        Ok(())
+    }
+
+    fn random_cave(seed: u64) -> Vec<String> {
+        let mut rng = Rng::new(seed);
+
+        (0..rng.gen_range(1..12))
+            .map(|_| {
+                let mut col = rng.gen_range(470..530);
+                let mut row = rng.gen_range(1..40);
+                let mut points = vec![format!("{col},{row}")];
+
+                for _ in 0..rng.gen_range(1..4) {
+                    let length = rng.gen_range(0..8);
+                    if rng.gen_bool(0.5) {
+                        col = if rng.gen_bool(0.5) {
+                            col + length
+                        } else {
+                            col - length
+                        };
+                    } else {
+                        row += length;
+                    }
+                    points.push(format!("{col},{row}"));
+                }
+
+                points.join(" -> ")
+            })
+            .collect()
+    }
+
+    #[test]
+    fn floor_fill_count_matches_simulation() -> Result<()> {
+        for seed in 0..50 {
+            let lines = random_cave(seed);
+            let mut sand_world =
+                SandWorld::from_lines(&lines)?.with_config(SandConfig::with_floor())?;
+            let expected = sand_world.floor_fill_count();
+
+            assert_eq!(Some(sand_world.fill_until_blocked()), expected, "{lines:?}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn variant_configs_match_floor_fill_count() -> Result<()> {
+        let mut rng = Rng::new(99);
+
+        for seed in 0..50 {
+            let lines = random_cave(seed);
+            let sources = (0..rng.gen_range(1..4))
+                .map(|_| Position::from((rng.gen_range(0..5), rng.gen_range(480..520))))
+                .collect();
+            let mut moves = vec![(1, 0), (1, -1), (1, 1), (1, -2), (1, 2), (2, 0)];
+            rng.shuffle(&mut moves);
+            moves.truncate(rng.gen_range(1..moves.len()));
+
+            let config = SandConfig {
+                sources,
+                moves,
+                floor_offset: Some(rng.gen_range(1..5)),
+            };
+            let mut sand_world = SandWorld::from_lines(&lines)?.with_config(config.clone())?;
+            let expected = sand_world.floor_fill_count();
+
+            assert_eq!(
+                Some(sand_world.fill_until_blocked()),
+                expected,
+                "{lines:?} {config:?}"
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn multiple_sources_take_turns() -> Result<()> {
+        let config = SandConfig {
+            sources: vec![Position::from((0, 495)), Position::from((0, 500))],
+            ..SandConfig::default()
+        };
+        let mut sand_world = load_input("day_14_example")?.with_config(config)?;
+
+        let spawned: Vec<SandEvent> = sand_world
+            .events()
+            .filter(|event| matches!(event, SandEvent::Spawned(_)))
+            .take(3)
+            .collect();
+
+        assert_eq!(
+            spawned,
+            vec![
+                SandEvent::Spawned(Position::from((0, 495))),
+                SandEvent::Spawned(Position::from((0, 500))),
+                SandEvent::Spawned(Position::from((0, 495))),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_configs() -> Result<()> {
+        let sideways = SandConfig {
+            moves: vec![(1, 0), (0, 1)],
+            ..SandConfig::default()
+        };
+        assert!(load_input("day_14_example")?.with_config(sideways).is_err());
+
+        let no_sources = SandConfig {
+            sources: vec![],
+            ..SandConfig::default()
+        };
+        assert!(load_input("day_14_example")?
+            .with_config(no_sources)
+            .is_err());
+
+        let floor_on_rock = SandConfig {
+            floor_offset: Some(0),
+            ..SandConfig::default()
+        };
+        assert!(load_input("day_14_example")?
+            .with_config(floor_on_rock)
+            .is_err());
+        Ok(())
+    }
+
+    fn lines(lines: &[&str]) -> Vec<String> {
+        lines.iter().map(|line| line.to_string()).collect()
+    }
+
+    #[test]
+    fn rock_paths_round_trip() -> Result<()> {
+        let input = read_input_lines("day_14");
+        let sand_world = SandWorld::from_lines(&input)?;
+
+        let serialised: Vec<String> = sand_world
+            .rock_paths
+            .iter()
+            .map(|path| path.to_string())
+            .collect();
+
+        assert_eq!(serialised, input);
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_invalid_rock_paths() {
+        let diagonal = SandWorld::from_lines(&lines(&["498,4 -> 498,6", "498,6 -> 500,8"]));
+        let error = format!("{:#}", diagonal.err().unwrap());
+        assert!(error.contains("line 2"), "{error}");
+        assert!(
+            error.contains("Diagonal segment from 498,6 to 500,8"),
+            "{error}"
+        );
+
+        let bad_number = SandWorld::from_lines(&lines(&["498,4 -> 498,x6"]));
+        let error = format!("{:#}", bad_number.err().unwrap());
+        assert!(error.contains("line 1"), "{error}");
+        assert!(error.contains("Invalid y coordinate \"x6\""), "{error}");
+
+        let missing_comma = SandWorld::from_lines(&lines(&["", "498 -> 498,6"]));
+        let error = format!("{:#}", missing_comma.err().unwrap());
+        assert!(error.contains("line 2"), "{error}");
+
+        assert!(SandWorld::from_lines(&lines(&["-1,4 -> 2,4"])).is_err());
+        assert!(SandWorld::from_lines(&lines(&[""])).is_err());
+    }
+
+    #[test]
+    fn dedupes_overlapping_segments() -> Result<()> {
+        let paths = parse_rock_paths(&lines(&[
+            "498,4 -> 498,6 -> 496,6",
+            "498,5 -> 498,9",
+            "495,6 -> 497,6",
+            "503,4 -> 502,4",
+            "503,4 -> 502,4",
+            "510,2",
+        ]))?;
+
+        let segments: Vec<String> = dedupe_segments(&paths)
+            .into_iter()
+            .map(|segment| RockPath::from(segment).to_string())
+            .collect();
+
+        assert_eq!(
+            segments,
+            [
+                "510,2 -> 510,2",
+                "502,4 -> 503,4",
+                "495,6 -> 498,6",
+                "498,4 -> 498,9",
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn dense_storage() -> Result<()> {
+        let mut sand_world = load_input("day_14")?.with_storage::<DenseTiles>();
+        assert_eq!(sand_world.fill_until_blocked(), 825);
+
+        let mut sand_world = load_input("day_14")?
+            .with_storage::<DenseTiles>()
+            .with_config(SandConfig::with_floor())?;
+        assert_eq!(sand_world.floor_fill_count(), Some(26729));
+        assert_eq!(sand_world.fill_until_blocked(), 26729);
+        Ok(())
+    }
+
+    #[test]
+    fn dense_storage_matches_sparse() -> Result<()> {
+        let mut rng = Rng::new(39);
+
+        for seed in 0..30 {
+            let lines = random_cave(seed);
+            let config = SandConfig {
+                sources: vec![Position::from((0, rng.gen_range(490..510)))],
+                moves: vec![(1, 0), (1, -2), (1, 2)],
+                floor_offset: if rng.gen_bool(0.5) { Some(2) } else { None },
+            };
+
+            let mut sparse = SandWorld::from_lines(&lines)?.with_config(config.clone())?;
+            let mut dense = SandWorld::from_lines(&lines)?
+                .with_config(config)?
+                .with_storage::<DenseTiles>();
+
+            assert_eq!(sparse.fill_until_blocked(), dense.fill_until_blocked());
+
+            let mut sparse_tiles = sparse.tiles.tiles();
+            let mut dense_tiles = dense.tiles.tiles();
+            sparse_tiles.sort_by_key(|(position, _)| (position.row, position.col));
+            dense_tiles.sort_by_key(|(position, _)| (position.row, position.col));
+            assert_eq!(sparse_tiles, dense_tiles, "{lines:?}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn resume_from_snapshot() -> Result<()> {
+        let mut sand_world = load_input("day_14")?.with_config(SandConfig::with_floor())?;
+        for _ in 0..10000 {
+            sand_world.drop_sand();
+        }
+
+        let json = sand_world.snapshot().to_json()?;
+        let mut restored = SandWorld::from_snapshot(Snapshot::from_json(&json)?)?;
+
+        assert_eq!(restored.grains(), 10000);
+        assert_eq!(restored.tiles, sand_world.tiles);
+        restored.fill_until_blocked();
+        assert_eq!(restored.grains(), 26729);
+        Ok(())
+    }
+
+    #[test]
+    fn snapshot_file_mid_fall() -> Result<()> {
+        let path = std::env::temp_dir().join("day_14_example_snapshot.json");
+        let mut sand_world = load_input("day_14_example")?;
+        for _ in 0..5 {
+            sand_world.drop_sand();
+        }
+        while sand_world.step() != SandEvent::Moved(Position::from((3, 500))) {}
+
+        sand_world.save_snapshot(&path)?;
+        let mut restored = SandWorld::load_snapshot(&path)?.with_storage::<DenseTiles>();
+        std::fs::remove_file(&path)?;
+
+        assert_eq!(restored.snapshot(), sand_world.snapshot());
+        assert_eq!(restored.step(), sand_world.step());
+        assert_eq!(
+            restored.fill_until_blocked(),
+            sand_world.fill_until_blocked()
+        );
+        assert_eq!(restored.grains(), 24);
+        Ok(())
+    }
+
+    #[test]
+    fn snapshot_keeps_source_turn() -> Result<()> {
+        let config = SandConfig {
+            sources: vec![Position::from((0, 495)), Position::from((0, 500))],
+            ..SandConfig::with_floor()
+        };
+        let mut sand_world = load_input("day_14_example")?.with_config(config)?;
+        sand_world.drop_sand();
+
+        let mut restored = SandWorld::from_snapshot(sand_world.snapshot())?;
+        assert_eq!(restored.snapshot(), sand_world.snapshot());
+        for _ in 0..50 {
+            assert_eq!(restored.step(), sand_world.step());
+        }
+        assert_eq!(
+            restored.fill_until_blocked(),
+            sand_world.fill_until_blocked()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_invalid_snapshots() -> Result<()> {
+        let mut snapshot = load_input("day_14_example")?.snapshot();
+        snapshot.sand.push(Position::from((4, 498)));
+        assert!(SandWorld::from_snapshot(snapshot).is_err());
+
+        let mut snapshot = load_input("day_14_example")?.snapshot();
+        snapshot.next_source = 1;
+        assert!(SandWorld::from_snapshot(snapshot).is_err());
+
+        let json = r#"{"config":{"sources":[{"row":0,"col":500}],"moves":[[1,0]],"floor_offset":null},"rock_paths":["1,1 -> 2,2"],"sand":[],"falling":null,"next_source":0,"grains":0,"moves":0}"#;
+        assert!(Snapshot::from_json(json).is_err());
+        Ok(())
+    }
 }
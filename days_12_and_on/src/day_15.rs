@@ -1,9 +1,22 @@
-use std::ops::Range;
-
-use rayon::{
-    prelude::{IntoParallelIterator, ParallelIterator},
-    vec,
+use std::{
+    collections::{BTreeSet, HashSet},
+    fmt::{Debug, Display},
+    fs::write,
+    hash::Hash,
+    iter::from_fn,
+    mem::size_of,
+    num::ParseIntError,
+    ops::RangeInclusive,
+    path::Path,
+    str::FromStr,
+    time::Instant,
 };
+
+use anyhow::{anyhow, Context, Result};
+
+use interval_set::IntervalSet;
+use num_traits::{Euclid, PrimInt, Signed};
+use rayon::prelude::{IntoParallelIterator, ParallelIterator};
 use regex::Regex;
 
 use crate::utils::read_input_lines;
@@ -12,185 +25,1344 @@ const INPUT_PATTERN: &str =
     "Sensor at x=(?P<sensor_x>-?[0-9]+), y=(?P<sensor_y>-?[0-9]+): closest beacon is at x=(?P<beacon_x>-?[0-9]+), y=(?P<beacon_y>-?[0-9]+)";
 //   Sensor at x=24822222222222223411, y=39222222222222202983: closest beacon is at x=22895722222222222229, y=36322223423423423785
 
-#[derive(Debug)]
-struct Point {
-    x: i32,
-    y: i32,
+// Signed integers wide enough for the input. `i64` covers the puzzle, `i128` covers inputs
+// like the one above.
+trait Coord:
+    PrimInt + Signed + Euclid + FromStr<Err = ParseIntError> + Hash + Debug + Display + Send + Sync
+{
+}
+
+impl<T> Coord for T where
+    T: PrimInt
+        + Signed
+        + Euclid
+        + FromStr<Err = ParseIntError>
+        + Hash
+        + Debug
+        + Display
+        + Send
+        + Sync
+{
+}
+
+fn two<T: Coord>() -> T {
+    T::one() + T::one()
+}
+
+fn abs_diff<T: Coord>(a: T, b: T) -> Option<T> {
+    if a > b {
+        a.checked_sub(&b)
+    } else {
+        b.checked_sub(&a)
+    }
 }
 
-impl Point {
-    fn new(x: i32, y: i32) -> Self {
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Point<T = i64> {
+    x: T,
+    y: T,
+}
+
+impl<T: Coord> Point<T> {
+    fn new(x: T, y: T) -> Self {
         Self { x, y }
     }
 
-    fn dist(&self, other: &Point) -> i32 {
+    fn dist(&self, other: &Point<T>) -> T {
         (self.x - other.x).abs() + (self.y - other.y).abs()
     }
+
+    fn checked_dist(&self, other: &Point<T>) -> Option<T> {
+        abs_diff(self.x, other.x)?.checked_add(&abs_diff(self.y, other.y)?)
+    }
+
+    fn tuning_frequency(&self) -> Option<T> {
+        self.x.checked_mul(&T::from(4000000)?)?.checked_add(&self.y)
+    }
 }
 
-impl From<(i32, i32)> for Point {
-    fn from(value: (i32, i32)) -> Self {
+impl<T: Coord> From<(T, T)> for Point<T> {
+    fn from(value: (T, T)) -> Self {
         Point::new(value.0, value.1)
     }
 }
 
 #[derive(Debug)]
-struct Sensor {
-    location: Point,
-    closest_beacon: Point,
+struct Sensor<T = i64> {
+    location: Point<T>,
+    closest_beacon: Point<T>,
 }
 
-impl Sensor {
-    fn range(&self) -> i32 {
+impl<T: Coord> Sensor<T> {
+    fn range(&self) -> T {
         self.location.dist(&self.closest_beacon)
     }
 
-    fn coverage_at_y(&self, y: &i32) -> Option<Range<i32>> {
-        let y_dist = (self.location.y - y).abs();
+    fn coverage_at_y(&self, y: &T) -> Option<RangeInclusive<T>> {
+        let y_dist = abs_diff(self.location.y, *y)?;
         let x_dist = self.range() - y_dist;
 
-        if x_dist < 0 {
+        if x_dist < T::zero() {
             return None;
         }
 
         let start = self.location.x - x_dist;
         let end = self.location.x + x_dist;
 
-        Some(start..end)
+        Some(start..=end)
+    }
+
+    fn coverage_at_x(&self, x: &T) -> Option<RangeInclusive<T>> {
+        let x_dist = abs_diff(self.location.x, *x)?;
+        let y_dist = self.range() - x_dist;
+
+        if y_dist < T::zero() {
+            return None;
+        }
+
+        let start = self.location.y - y_dist;
+        let end = self.location.y + y_dist;
+
+        Some(start..=end)
+    }
+
+    fn covers(&self, point: &Point<T>) -> bool {
+        self.location
+            .checked_dist(point)
+            .is_some_and(|dist| dist <= self.range())
     }
+
+    // Everything worked out from a sensor lies inside the lines just outside its range, so if
+    // those fit in `T` the rest of the geometry can't overflow.
+    fn check_bounds(&self) -> Result<()> {
+        let boundaries = || -> Option<[T; 4]> {
+            let dist = self
+                .location
+                .checked_dist(&self.closest_beacon)?
+                .checked_add(&T::one())?;
+            let u = self.location.x.checked_add(&self.location.y)?;
+            let v = self.location.x.checked_sub(&self.location.y)?;
+
+            Some([
+                u.checked_sub(&dist)?,
+                u.checked_add(&dist)?,
+                v.checked_sub(&dist)?,
+                v.checked_add(&dist)?,
+            ])
+        };
+
+        boundaries().map(|_| ()).ok_or_else(|| {
+            anyhow!(
+                "Sensor range doesn't fit in {}-bit coordinates",
+                size_of::<T>() * 8
+            )
+        })
+    }
+}
+
+fn parse_sensor<T: Coord>(re: &Regex, line: &str) -> Result<Sensor<T>> {
+    let capture = re.captures(line).ok_or_else(|| {
+        anyhow!("Expected \"Sensor at x=<x>, y=<y>: closest beacon is at x=<x>, y=<y>\"")
+    })?;
+
+    let coordinate = |name: &str| -> Result<T> {
+        let value = capture.name(name).unwrap().as_str();
+        value
+            .parse()
+            .with_context(|| format!("Invalid {} {value:?}", name.replace('_', " ")))
+    };
+
+    let sensor = Sensor {
+        location: Point::new(coordinate("sensor_x")?, coordinate("sensor_y")?),
+        closest_beacon: Point::new(coordinate("beacon_x")?, coordinate("beacon_y")?),
+    };
+    sensor.check_bounds()?;
+
+    Ok(sensor)
 }
 
-fn load_input(file_name: &str) -> Vec<Sensor> {
-    let re = Regex::new(INPUT_PATTERN).unwrap();
-    let lines = read_input_lines(file_name);
+fn parse_sensors<T: Coord>(lines: &[String]) -> Result<Vec<Sensor<T>>> {
+    let re = Regex::new(INPUT_PATTERN)?;
 
     lines
         .iter()
-        .map(|line| -> Sensor {
-            let capture = re.captures(line).unwrap();
-
-            Sensor {
-                location: Point {
-                    x: capture.name("sensor_x").unwrap().as_str().parse().unwrap(),
-                    y: capture.name("sensor_y").unwrap().as_str().parse().unwrap(),
-                },
-                closest_beacon: Point {
-                    x: capture.name("beacon_x").unwrap().as_str().parse().unwrap(),
-                    y: capture.name("beacon_y").unwrap().as_str().parse().unwrap(),
-                },
-            }
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| {
+            parse_sensor(&re, line).with_context(|| format!("Invalid sensor on line {}", index + 1))
         })
         .collect()
 }
 
-#[derive(Debug, Default)]
-struct RangeSet {
-    ranges: Vec<Range<i32>>,
+fn load_input<T: Coord>(file_name: &str) -> Result<Vec<Sensor<T>>> {
+    parse_sensors(&read_input_lines(file_name))
 }
 
-impl RangeSet {
-    fn insert(&mut self, range: Range<i32>) {
-        if range.end < range.start {
-            panic!("start must be less than or equal to end")
+fn coverage<T: Coord>(sensors: &[Sensor<T>], y: &T) -> IntervalSet<T> {
+    sensors
+        .iter()
+        .filter_map(|sensor| sensor.coverage_at_y(y))
+        .collect()
+}
+
+fn cannot_contain_beacon_count<T: Coord>(sensors: &[Sensor<T>], y: &T) -> u128 {
+    let coverage = coverage(sensors, y);
+
+    // Several sensors can share a closest beacon, so only count each beacon cell once.
+    let known_beacons: HashSet<T> = sensors
+        .iter()
+        .map(|sensor| &sensor.closest_beacon)
+        .filter(|beacon| beacon.y == *y && coverage.contains(&beacon.x))
+        .map(|beacon| beacon.x)
+        .collect();
+
+    coverage.len() - known_beacons.len() as u128
+}
+
+fn is_covered<T: Coord>(sensors: &[Sensor<T>], point: &Point<T>) -> bool {
+    sensors.iter().any(|sensor| sensor.covers(point))
+}
+
+// The cell at u = x + y, v = x - y, if there is one.
+fn from_rotated<T: Coord>(u: T, v: T) -> Option<Point<T>> {
+    let (sum, diff) = (u.checked_add(&v)?, u.checked_sub(&v)?);
+    (diff % two() == T::zero()).then(|| Point::new(sum / two(), diff / two()))
+}
+
+// The v ranges covered along the line x + y = u.
+fn covered_along_u<T: Coord>(rects: &[RotatedRect<T>], u: T) -> IntervalSet<T> {
+    rects
+        .iter()
+        .filter(|rect| rect.u.contains(&u))
+        .map(|rect| rect.v.clone())
+        .collect()
+}
+
+// The u ranges covered along the line x - y = v.
+fn covered_along_v<T: Coord>(rects: &[RotatedRect<T>], v: T) -> IntervalSet<T> {
+    rects
+        .iter()
+        .filter(|rect| rect.v.contains(&v))
+        .map(|rect| rect.u.clone())
+        .collect()
+}
+
+// The first value in `window` with the same parity as `line` that isn't in `covered`.
+fn first_uncovered_on_line<T: Coord>(
+    covered: &IntervalSet<T>,
+    line: T,
+    window: RangeInclusive<T>,
+) -> Option<T> {
+    let parity = line.rem_euclid(&two());
+
+    covered.gaps(window).find_map(|gap| {
+        let start = *gap.start();
+        let value = if start.rem_euclid(&two()) == parity {
+            Some(start)
+        } else {
+            start.checked_add(&T::one())
+        };
+        value.filter(|value| gap.contains(value))
+    })
+}
+
+// The value closest to `target` that has the same parity as `line` and isn't in `covered`.
+fn nearest_uncovered_on_line<T: Coord>(covered: &IntervalSet<T>, line: T, target: T) -> Option<T> {
+    let parity = line.rem_euclid(&two());
+    let start = covered
+        .min()
+        .unwrap_or(target)
+        .min(target)
+        .saturating_sub(two());
+    let end = covered
+        .max()
+        .unwrap_or(target)
+        .max(target)
+        .saturating_add(two());
+
+    covered
+        .gaps(start..=end)
+        .flat_map(|gap| {
+            let nearest = target.max(*gap.start()).min(*gap.end());
+            [
+                nearest.checked_sub(&T::one()),
+                Some(nearest),
+                nearest.checked_add(&T::one()),
+            ]
+            .into_iter()
+            .flatten()
+            .filter(move |value| gap.contains(value) && value.rem_euclid(&two()) == parity)
+        })
+        .filter_map(|value| Some((abs_diff(value, target)?, value)))
+        .min()
+        .map(|(_, value)| value)
+}
+
+#[derive(Debug)]
+struct SensorField<T = i64> {
+    sensors: Vec<Sensor<T>>,
+}
+
+impl<T: Coord> From<Vec<Sensor<T>>> for SensorField<T> {
+    fn from(sensors: Vec<Sensor<T>>) -> Self {
+        Self { sensors }
+    }
+}
+
+impl<T: Coord> SensorField<T> {
+    fn load(file_name: &str) -> Result<Self> {
+        Ok(load_input(file_name)?.into())
+    }
+
+    fn covering<'a>(&'a self, point: &'a Point<T>) -> impl Iterator<Item = &'a Sensor<T>> + 'a {
+        self.sensors.iter().filter(|sensor| sensor.covers(point))
+    }
+
+    fn is_covered(&self, point: &Point<T>) -> bool {
+        is_covered(&self.sensors, point)
+    }
+
+    fn row_coverage(&self, y: &T) -> IntervalSet<T> {
+        coverage(&self.sensors, y)
+    }
+
+    fn column_coverage(&self, x: &T) -> IntervalSet<T> {
+        self.sensors
+            .iter()
+            .filter_map(|sensor| sensor.coverage_at_x(x))
+            .collect()
+    }
+
+    // The nearest uncovered cell is either `point` itself or next to a covered cell, which puts
+    // it on one of the lines just outside some sensor's range. Along each of those lines the
+    // covered cells form a few ranges, so the closest uncovered cell on every line is a candidate.
+    fn nearest_uncovered(&self, point: &Point<T>) -> Option<Point<T>> {
+        if !self.is_covered(point) {
+            return Some(*point);
         }
 
-        let mut overlapping_ranges: Vec<&Range<i32>> = self
-            .ranges
+        let rects: Vec<RotatedRect<T>> = self.sensors.iter().map(RotatedRect::from).collect();
+        let u = point.x.checked_add(&point.y)?;
+        let v = point.x.checked_sub(&point.y)?;
+
+        let candidates = self.sensors.iter().flat_map(|sensor| {
+            let Point { x, y } = sensor.location;
+            let dist = sensor.range() + T::one();
+
+            let along_u = [x + y - dist, x + y + dist].map(|line| {
+                nearest_uncovered_on_line(&covered_along_u(&rects, line), line, v)
+                    .and_then(|v| from_rotated(line, v))
+            });
+            let along_v = [x - y - dist, x - y + dist].map(|line| {
+                nearest_uncovered_on_line(&covered_along_v(&rects, line), line, u)
+                    .and_then(|u| from_rotated(u, line))
+            });
+
+            along_u.into_iter().chain(along_v).flatten()
+        });
+
+        candidates
+            .filter_map(|candidate| {
+                Some((candidate.checked_dist(point)?, candidate.y, candidate.x))
+            })
+            .min()
+            .map(|(_, y, x)| Point::new(x, y))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum GapStrategy {
+    RowScan,
+    Boundaries,
+}
+
+fn first_gap<T: Coord>(sensors: &[Sensor<T>], max: &T, strategy: GapStrategy) -> Option<Point<T>>
+where
+    RangeInclusive<T>: IntoParallelIterator<Item = T>,
+{
+    match strategy {
+        GapStrategy::RowScan => first_gap_by_rows(sensors, max),
+        GapStrategy::Boundaries => first_gap_by_boundaries(sensors, max),
+    }
+}
+
+fn first_gap_by_rows<T: Coord>(sensors: &[Sensor<T>], max: &T) -> Option<Point<T>>
+where
+    RangeInclusive<T>: IntoParallelIterator<Item = T>,
+{
+    (T::zero()..=*max).into_par_iter().find_map_any(|y| {
+        coverage(sensors, &y)
+            .gaps(T::zero()..=*max)
+            .next()
+            .map(|gap| Point::new(*gap.start(), y))
+    })
+}
+
+// Just outside a sensor's range, the edges of its diamond lie on the lines x + y = u and
+// x - y = v. An uncovered cell next to a covered one is at exactly that distance from its
+// sensor, so unless nothing in the search area is covered, some gap lies on one of those lines.
+fn first_gap_by_boundaries<T: Coord>(sensors: &[Sensor<T>], max: &T) -> Option<Point<T>> {
+    let rects: Vec<RotatedRect<T>> = sensors.iter().map(RotatedRect::from).collect();
+    let mut u_lines = BTreeSet::new();
+    let mut v_lines = BTreeSet::new();
+
+    sensors.iter().for_each(|sensor| {
+        let Point { x, y } = sensor.location;
+        let dist = sensor.range() + T::one();
+        u_lines.extend([x + y - dist, x + y + dist]);
+        v_lines.extend([x - y - dist, x - y + dist]);
+    });
+
+    let (zero, max) = (T::zero(), *max);
+    let double_max = max.checked_add(&max)?;
+
+    // Inside the search area, x + y = u spans v from |u| up to whichever edge it hits first,
+    // and x - y = v spans u the same way.
+    let along_u = u_lines.iter().filter_map(|&u| {
+        let window = zero.checked_sub(&u)?.max(u.checked_sub(&double_max)?)
+            ..=u.min(double_max.checked_sub(&u)?);
+        let v = first_uncovered_on_line(&covered_along_u(&rects, u), u, window)?;
+        from_rotated(u, v)
+    });
+    let along_v = v_lines.iter().filter_map(|&v| {
+        let window = zero.checked_sub(&v)?.max(v)
+            ..=double_max.checked_sub(&v)?.min(v.checked_add(&double_max)?);
+        let u = first_uncovered_on_line(&covered_along_v(&rects, v), v, window)?;
+        from_rotated(u, v)
+    });
+
+    along_u
+        .chain(along_v)
+        .chain([Point::new(zero, zero)])
+        .find(|point| !is_covered(sensors, point))
+}
+
+// Under u = x + y, v = x - y every sensor's diamond becomes an axis-aligned square. Only the
+// (u, v) pairs where u and v have the same parity are real cells.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct RotatedRect<T> {
+    u: RangeInclusive<T>,
+    v: RangeInclusive<T>,
+}
+
+impl<T: Coord> From<&Sensor<T>> for RotatedRect<T> {
+    fn from(sensor: &Sensor<T>) -> Self {
+        let Point { x, y } = sensor.location;
+        let range = sensor.range();
+
+        Self {
+            u: (x + y - range)..=(x + y + range),
+            v: (x - y - range)..=(x - y + range),
+        }
+    }
+}
+
+fn parity_count<T: Coord>(range: &RangeInclusive<T>, parity: T) -> u128 {
+    if range.is_empty() {
+        return 0;
+    }
+
+    let offset = if range.start().rem_euclid(&two()) == parity {
+        T::zero()
+    } else {
+        T::one()
+    };
+
+    match range.start().checked_add(&offset) {
+        Some(first) if first <= *range.end() => {
+            let first = first.to_i128().unwrap() as u128;
+            let end = range.end().to_i128().unwrap() as u128;
+            end.wrapping_sub(first) / 2 + 1
+        }
+        _ => 0,
+    }
+}
+
+fn ceil_div(value: i128, divisor: i128) -> i128 {
+    let quotient = value.div_euclid(divisor);
+    if value.rem_euclid(divisor) == 0 {
+        quotient
+    } else {
+        quotient + 1
+    }
+}
+
+// The line `offset + slope * u` in rotated space.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Line {
+    offset: i128,
+    slope: i128,
+}
+
+impl Line {
+    fn new(offset: i128, slope: i128) -> Self {
+        Self { offset, slope }
+    }
+
+    fn at(&self, u: i128) -> Option<i128> {
+        self.slope.checked_mul(u)?.checked_add(self.offset)
+    }
+
+    // Floor of the u where the two lines meet, if they aren't parallel.
+    fn crossing(&self, other: &Line) -> Option<i128> {
+        let (offset, slope) = match self.slope - other.slope {
+            0 => return None,
+            slope if slope < 0 => (self.offset.checked_sub(other.offset)?, -slope),
+            slope => (other.offset.checked_sub(self.offset)?, slope),
+        };
+
+        Some(offset.div_euclid(slope))
+    }
+}
+
+// The line in `lines` that is on the `pick` side of all the others at both ends of `start..=end`.
+fn extreme_line(
+    lines: &[Line],
+    start: i128,
+    end: i128,
+    pick: fn(i128, i128) -> bool,
+) -> Option<Line> {
+    lines.iter().copied().find(|line| {
+        lines.iter().all(|other| {
+            [start, end]
+                .iter()
+                .all(|&u| match (line.at(u), other.at(u)) {
+                    (Some(value), Some(other)) => value == other || pick(value, other),
+                    _ => false,
+                })
+        })
+    })
+}
+
+// Number of cells with the same parity of u and v, u in `start..=end` and v between `lower`
+// and `upper`, which are straight on that range.
+fn count_under(lower: Line, upper: Line, start: i128, end: i128) -> Option<i128> {
+    [0, 1].iter().try_fold(0i128, |total, &parity| {
+        // With u = 2k + parity and v = 2j + parity, the number of j for each k is linear in k.
+        let mut first = ceil_div(start - parity, 2);
+        let mut last = (end - parity).div_euclid(2);
+
+        let base = (upper.at(parity)? - parity).div_euclid(2)
+            - ceil_div(lower.at(parity)? - parity, 2)
+            + 1;
+        let growth = upper.slope - lower.slope;
+
+        match growth {
+            0 if base <= 0 => return Some(total),
+            0 => {}
+            growth if growth > 0 => first = first.max(ceil_div(1 - base, growth)),
+            growth => last = last.min((base - 1).div_euclid(-growth)),
+        }
+
+        if first > last {
+            return Some(total);
+        }
+
+        let len = last - first + 1;
+        let k_sum = first.checked_add(last)?.checked_mul(len)? / 2;
+        let sum = base
+            .checked_mul(len)?
+            .checked_add(growth.checked_mul(k_sum)?)?;
+        total.checked_add(sum)
+    })
+}
+
+// Number of cells with u in `us` and v between the largest of `lowers` and the smallest of
+// `uppers`. The bounds are cut where any two of them cross so each piece is a pair of lines.
+fn count_between(us: RangeInclusive<i128>, lowers: &[Line], uppers: &[Line]) -> Option<i128> {
+    if us.is_empty() {
+        return Some(0);
+    }
+    let (start, end) = (*us.start(), *us.end());
+
+    let mut starts: BTreeSet<i128> = [lowers, uppers]
+        .iter()
+        .flat_map(|lines| {
+            lines
+                .iter()
+                .flat_map(move |line| lines.iter().filter_map(move |other| line.crossing(other)))
+        })
+        .flat_map(|u| [u, u.saturating_add(1)])
+        .filter(|u| start < *u && *u <= end)
+        .collect();
+    starts.insert(start);
+
+    let ends = starts.iter().skip(1).map(|next| next - 1).chain([end]);
+
+    starts
+        .iter()
+        .zip(ends)
+        .try_fold(0i128, |total, (&start, end)| {
+            let lower = extreme_line(lowers, start, end, |value, other| value > other)?;
+            let upper = extreme_line(uppers, start, end, |value, other| value < other)?;
+            total.checked_add(count_under(lower, upper, start, end)?)
+        })
+}
+
+fn to_i128<T: Coord>(range: &RangeInclusive<T>) -> RangeInclusive<i128> {
+    range.start().to_i128().unwrap()..=range.end().to_i128().unwrap()
+}
+
+// The box `min..=max` in rotated space: a diamond over `us`, with v bounded below by the larger
+// of `lowers` and above by the smaller of `uppers`.
+#[derive(Debug, Clone)]
+struct Diamond {
+    us: RangeInclusive<i128>,
+    lowers: [Line; 2],
+    uppers: [Line; 2],
+}
+
+impl Diamond {
+    fn new<T: Coord>(min: &Point<T>, max: &Point<T>) -> Option<Self> {
+        let [x0, y0, x1, y1] = [min.x, min.y, max.x, max.y].map(|value| value.to_i128().unwrap());
+        let double = |value: i128| value.checked_mul(2);
+
+        Some(Self {
+            us: x0.checked_add(y0)?..=x1.checked_add(y1)?,
+            lowers: [
+                Line::new(double(x0)?, -1),
+                Line::new(double(y1)?.checked_neg()?, 1),
+            ],
+            uppers: [
+                Line::new(double(x1)?, -1),
+                Line::new(double(y0)?.checked_neg()?, 1),
+            ],
+        })
+    }
+
+    // Cells of the box with u in `us` and, if given, v in `vs`.
+    fn count(&self, us: &RangeInclusive<i128>, vs: Option<&RangeInclusive<i128>>) -> Option<i128> {
+        let us = *us.start().max(self.us.start())..=*us.end().min(self.us.end());
+
+        let mut lowers = self.lowers.to_vec();
+        let mut uppers = self.uppers.to_vec();
+        if let Some(vs) = vs {
+            lowers.push(Line::new(*vs.start(), 0));
+            uppers.push(Line::new(*vs.end(), 0));
+        }
+
+        count_between(us, &lowers, &uppers)
+    }
+
+    // Cells of the box with u in `us` that aren't in any of the `covered` v ranges.
+    fn uncovered(&self, us: &RangeInclusive<i128>, covered: &IntervalSet<i128>) -> Option<i128> {
+        covered.iter().try_fold(self.count(us, None)?, |total, vs| {
+            total.checked_sub(self.count(us, Some(&vs))?)
+        })
+    }
+}
+
+// The union of the sensors' squares, cut into vertical slabs of u over which the covered v
+// ranges don't change.
+#[derive(Debug, Clone)]
+struct RotatedCoverage<T: Coord> {
+    slabs: Vec<(RangeInclusive<T>, IntervalSet<T>)>,
+}
+
+impl<T: Coord> RotatedCoverage<T> {
+    fn from_sensors(sensors: &[Sensor<T>]) -> Self {
+        let rects: Vec<RotatedRect<T>> = sensors.iter().map(RotatedRect::from).collect();
+
+        let edges: BTreeSet<T> = rects
             .iter()
-            .filter(|other| range.start - 1 <= other.end && other.start <= range.end + 1)
+            .flat_map(|rect| [*rect.u.start(), *rect.u.end() + T::one()])
             .collect();
 
-        overlapping_ranges.push(&range);
+        let slabs = edges
+            .iter()
+            .zip(edges.iter().skip(1))
+            .map(|(start, next)| {
+                let covered: IntervalSet<T> = rects
+                    .iter()
+                    .filter(|rect| rect.u.contains(start))
+                    .map(|rect| rect.v.clone())
+                    .collect();
+                (*start..=(*next - T::one()), covered)
+            })
+            .filter(|(_, covered)| !covered.is_empty())
+            .collect();
 
-        let min = overlapping_ranges.iter().map(|r| r.start).min();
-        let max = overlapping_ranges.iter().map(|r| r.end).max();
+        Self { slabs }
+    }
 
-        // Remove overlapping ranges
-        self.ranges
-            .retain(|other| range.start - 1 > other.end || other.start > range.end + 1);
+    // Number of cells covered by at least one sensor, or `None` if that doesn't fit in a u128.
+    fn area(&self) -> Option<u128> {
+        self.slabs.iter().try_fold(0u128, |total, (us, covered)| {
+            let even = parity_count(us, T::zero());
+            let odd = parity_count(us, T::one());
 
-        if let (Some(min), Some(max)) = (min, max) {
-            self.ranges.push(min..max);
-            self.ranges.sort_by(|a, b| a.start.cmp(&b.start))
-        }
+            covered.iter().try_fold(total, |total, vs| {
+                let even = even.checked_mul(parity_count(&vs, T::zero()))?;
+                let odd = odd.checked_mul(parity_count(&vs, T::one()))?;
+                total.checked_add(even)?.checked_add(odd)
+            })
+        })
+    }
+
+    // Number of cells in the box `min..=max` covered by at least one sensor, clipping each slab
+    // to the box, or `None` if that doesn't fit in a u128.
+    fn covered_in(&self, min: &Point<T>, max: &Point<T>) -> Option<u128> {
+        let diamond = Diamond::new(min, max)?;
+
+        self.slabs.iter().try_fold(0u128, |total, (us, covered)| {
+            covered.iter().try_fold(total, |total, vs| {
+                let count = diamond.count(&to_i128(us), Some(&to_i128(&vs)))?;
+                total.checked_add(u128::try_from(count).ok()?)
+            })
+        })
     }
 
-    fn len(&self) -> usize {
-        self.ranges.iter().map(|r| r.len()).sum()
+    fn uncovered_in(&self, min: &Point<T>, max: &Point<T>) -> Option<u128> {
+        let diamond = Diamond::new(min, max)?;
+        let cells = u128::try_from(diamond.count(&diamond.us, None)?).ok()?;
+
+        cells.checked_sub(self.covered_in(min, max)?)
+    }
+
+    // The box's u range, split into the slabs and the uncovered stretches between them.
+    fn segments(
+        &self,
+        us: &RangeInclusive<i128>,
+    ) -> Vec<(RangeInclusive<i128>, IntervalSet<i128>)> {
+        let mut segments = Vec::new();
+        let mut next = *us.start();
+
+        for (slab, covered) in &self.slabs {
+            let slab = to_i128(slab);
+            if *slab.end() < next {
+                continue;
+            }
+            if *slab.start() > *us.end() {
+                break;
+            }
+
+            if next < *slab.start() {
+                segments.push((next..=*slab.start() - 1, IntervalSet::new()));
+            }
+            let covered = covered.iter().map(|vs| to_i128(&vs)).collect();
+            segments.push((next.max(*slab.start())..=*slab.end().min(us.end()), covered));
+            next = *slab.end() + 1;
+        }
+
+        if next <= *us.end() {
+            segments.push((next..=*us.end(), IntervalSet::new()));
+        }
+        segments
     }
 
-    fn from_sensors(sensors: &Vec<Sensor>, y: &i32) -> Self {
-        let mut range_set = Self::default();
+    // Every cell of the box `min..=max` not covered by any sensor. Segments of u without gaps are
+    // skipped by counting, and the rest are halved down to single columns of u to list the cells.
+    fn gaps<'a>(&'a self, min: &Point<T>, max: &Point<T>) -> impl Iterator<Item = Point<T>> + 'a {
+        let diamond = Diamond::new(min, max);
+        let mut stack = match &diamond {
+            Some(diamond) => self.segments(&diamond.us),
+            None => Vec::new(),
+        };
+        stack.reverse();
 
-        sensors.iter().for_each(|sensor| {
-            if let Some(range) = sensor.coverage_at_y(y) {
-                range_set.insert(range);
+        let columns = from_fn(move || {
+            let diamond = diamond.as_ref()?;
+
+            while let Some((us, covered)) = stack.pop() {
+                if diamond.uncovered(&us, &covered) == Some(0) {
+                    continue;
+                }
+
+                let (start, end) = (*us.start(), *us.end());
+                if start == end {
+                    let window = diamond.lowers[0]
+                        .at(start)?
+                        .max(diamond.lowers[1].at(start)?)
+                        ..=diamond.uppers[0]
+                            .at(start)?
+                            .min(diamond.uppers[1].at(start)?);
+                    return Some((start, covered.gaps(window).collect::<Vec<_>>()));
+                }
+
+                let middle = (start >> 1) + (end >> 1) + (start & end & 1);
+                stack.push((middle + 1..=end, covered.clone()));
+                stack.push((start..=middle, covered));
             }
+
+            None
         });
 
-        range_set
+        columns.flat_map(|(u, gaps)| {
+            gaps.into_iter().flat_map(move |vs| {
+                let start = *vs.start() + (*vs.start() - u).rem_euclid(2);
+                (start..=*vs.end()).step_by(2).filter_map(move |v| {
+                    Some(Point::new(T::from((u + v) / 2)?, T::from((u - v) / 2)?))
+                })
+            })
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+struct SvgOptions<T> {
+    width: f64,
+    height: f64,
+    row: Option<T>,
+    gap: Option<Point<T>>,
+}
+
+// The whole field is scaled to fit `width` x `height` without stretching. Sensors, beacons and
+// the gap are drawn as fixed-size markers so they stay visible however far it's zoomed out.
+fn render_svg<T: Coord>(sensors: &[Sensor<T>], options: &SvgOptions<T>) -> String {
+    let diamonds: Vec<(f64, f64, f64)> = sensors
+        .iter()
+        .map(|sensor| {
+            let Point { x, y } = sensor.location;
+            (
+                x.to_f64().unwrap(),
+                y.to_f64().unwrap(),
+                sensor.range().to_f64().unwrap(),
+            )
+        })
+        .collect();
+
+    let bound = |f: fn(&(f64, f64, f64)) -> f64, pick: fn(f64, f64) -> f64, empty: f64| {
+        diamonds.iter().map(f).reduce(pick).unwrap_or(empty)
+    };
+    let min_x = bound(|(x, _, range)| x - range, f64::min, 0.0);
+    let max_x = bound(|(x, _, range)| x + range, f64::max, 1.0);
+    let min_y = bound(|(_, y, range)| y - range, f64::min, 0.0);
+    let max_y = bound(|(_, y, range)| y + range, f64::max, 1.0);
+
+    let scale =
+        (options.width / (max_x - min_x).max(1.0)).min(options.height / (max_y - min_y).max(1.0));
+    let project = |point: &Point<T>| {
+        (
+            (point.x.to_f64().unwrap() - min_x) * scale,
+            (point.y.to_f64().unwrap() - min_y) * scale,
+        )
+    };
+
+    let (width, height) = (options.width, options.height);
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"{height}\" viewBox=\"0 0 {width} {height}\">\n"
+    );
+    svg.push_str("<rect width=\"100%\" height=\"100%\" fill=\"#181824\"/>\n");
+
+    diamonds.iter().for_each(|(x, y, range)| {
+        let corners = [
+            (*x, y - range),
+            (x + range, *y),
+            (*x, y + range),
+            (x - range, *y),
+        ]
+        .map(|(x, y)| format!("{:.2},{:.2}", (x - min_x) * scale, (y - min_y) * scale));
+        svg.push_str(&format!(
+            "<polygon points=\"{}\" fill=\"#4a7bd0\" fill-opacity=\"0.25\" stroke=\"#4a7bd0\"/>\n",
+            corners.join(" ")
+        ));
+    });
+
+    if let Some(row) = options.row {
+        let (_, y) = project(&Point::new(T::zero(), row));
+        svg.push_str(&format!(
+            "<line x1=\"0\" y1=\"{y:.2}\" x2=\"{width}\" y2=\"{y:.2}\" stroke=\"#e8c060\"/>\n"
+        ));
     }
 
-    fn first_gap(&self, max: &i32) -> Option<i32> {
-        self.ranges
-            .get(0)
-            .map(|range| range.end + 1)
-            .filter(|value| value < max)
+    sensors.iter().for_each(|sensor| {
+        let (x, y) = project(&sensor.location);
+        svg.push_str(&format!(
+            "<circle cx=\"{x:.2}\" cy=\"{y:.2}\" r=\"3\" fill=\"#60d080\"/>\n"
+        ));
+    });
+
+    let beacons: BTreeSet<(T, T)> = sensors
+        .iter()
+        .map(|sensor| (sensor.closest_beacon.x, sensor.closest_beacon.y))
+        .collect();
+    beacons.into_iter().for_each(|beacon| {
+        let (x, y) = project(&Point::from(beacon));
+        svg.push_str(&format!(
+            "<rect x=\"{:.2}\" y=\"{:.2}\" width=\"6\" height=\"6\" fill=\"#e05050\"/>\n",
+            x - 3.0,
+            y - 3.0
+        ));
+    });
+
+    if let Some(gap) = &options.gap {
+        let (x, y) = project(gap);
+        svg.push_str(&format!(
+            "<circle cx=\"{x:.2}\" cy=\"{y:.2}\" r=\"8\" fill=\"none\" stroke=\"#ffffff\" stroke-width=\"2\"/>\n"
+        ));
     }
+
+    svg.push_str("</svg>\n");
+    svg
 }
 
-fn cannot_contain_beacon_count(sensors: &Vec<Sensor>, y: &i32) -> i32 {
-    RangeSet::from_sensors(sensors, y).len() as i32
+pub fn svg_day_15(
+    file_name: &str,
+    path: &Path,
+    width: f64,
+    height: f64,
+    row: i64,
+    max: i64,
+) -> Result<()> {
+    let sensors: Vec<Sensor> = load_input(file_name)?;
+    let gap = first_gap(&sensors, &max, GapStrategy::Boundaries);
+
+    let svg = render_svg(
+        &sensors,
+        &SvgOptions {
+            width,
+            height,
+            row: Some(row),
+            gap,
+        },
+    );
+    write(path, svg).with_context(|| format!("Couldn't write {}", path.display()))
 }
 
-fn first_gap(sensors: &Vec<Sensor>, max: &i32) -> Option<Point> {
-    (0..*max).into_par_iter().find_map_any(|y| {
-        if let Some(x) = RangeSet::from_sensors(sensors, &y).first_gap(max) {
-            return Some(Point::new(x, y));
+pub fn query_day_15(file_name: &str, query: &[&str]) -> Result<()> {
+    let field: SensorField = SensorField::load(file_name)?;
+
+    match query {
+        ["covering", x, y] => {
+            let point = Point::new(x.parse()?, y.parse()?);
+            let sensors: Vec<&Sensor> = field.covering(&point).collect();
+            println!("{point:?} is covered by {} sensors", sensors.len());
+            sensors.iter().for_each(|sensor| println!("{sensor:?}"));
+        }
+        ["nearest", x, y] => {
+            let point = Point::new(x.parse()?, y.parse()?);
+            println!("{:?}", field.nearest_uncovered(&point));
+        }
+        ["row", y] => {
+            let ranges: Vec<_> = field.row_coverage(&y.parse()?).iter().collect();
+            println!("{ranges:?}");
         }
+        ["column", x] => {
+            let ranges: Vec<_> = field.column_coverage(&x.parse()?).iter().collect();
+            println!("{ranges:?}");
+        }
+        _ => return Err(anyhow!("Unknown query {query:?}")),
+    }
 
-        None
-    })
+    Ok(())
+}
+
+pub fn bench_day_15(file_name: &str, max: i64) -> Result<()> {
+    let sensors: Vec<Sensor> = load_input(file_name)?;
+
+    for strategy in [GapStrategy::RowScan, GapStrategy::Boundaries] {
+        let now = Instant::now();
+        let point = first_gap(&sensors, &max, strategy);
+        let elapsed = now.elapsed();
+
+        let frequency = point.and_then(|point| point.tuning_frequency());
+        println!("{strategy:?}: {point:?}, tuning frequency {frequency:?} in {elapsed:?}");
+    }
+
+    let (min, max) = (Point::new(0, 0), Point::new(max, max));
+
+    let now = Instant::now();
+    let coverage = RotatedCoverage::from_sensors(&sensors);
+    let area = coverage.area();
+    let uncovered = coverage.uncovered_in(&min, &max);
+    let gaps: Vec<Point> = coverage.gaps(&min, &max).take(10).collect();
+    let elapsed = now.elapsed();
+
+    println!("Rotated union: {area:?} covered, {uncovered:?} uncovered {gaps:?} in {elapsed:?}");
+
+    Ok(())
 }
 
 #[cfg(test)]
 
 mod tests {
     use super::*;
+    use crate::utils::rng::Rng;
     use anyhow::{Ok, Result};
 
     #[test]
     fn example_part_1() -> Result<()> {
-        let sensors = load_input("day_15_example");
-        let result: i32 = cannot_contain_beacon_count(&sensors, &10);
+        let sensors: Vec<Sensor> = load_input("day_15_example")?;
+        let result = cannot_contain_beacon_count(&sensors, &10);
         assert_eq!(result, 26);
         Ok(())
     }
 
     #[test]
     fn part_1() -> Result<()> {
-        let sensors = load_input("day_15");
-        let result: i32 = cannot_contain_beacon_count(&sensors, &2000000);
+        let sensors: Vec<Sensor> = load_input("day_15")?;
+        let result = cannot_contain_beacon_count(&sensors, &2000000);
         assert_eq!(result, 5100463);
         Ok(())
     }
 
+    fn sensor(location: (i64, i64), closest_beacon: (i64, i64)) -> Sensor {
+        Sensor {
+            location: location.into(),
+            closest_beacon: closest_beacon.into(),
+        }
+    }
+
+    #[test]
+    fn row_without_beacons() -> Result<()> {
+        let sensors = vec![sensor((0, 0), (3, 0)), sensor((10, 0), (10, 2))];
+        assert_eq!(cannot_contain_beacon_count(&sensors, &1), 5 + 3);
+        Ok(())
+    }
+
+    #[test]
+    fn row_with_several_beacons() -> Result<()> {
+        let sensors = vec![
+            sensor((0, 0), (3, 0)),
+            sensor((4, 1), (5, 0)),
+            sensor((8, 2), (5, 0)),
+        ];
+        // -3..=11, minus the beacons at x = 3 and x = 5
+        assert_eq!(cannot_contain_beacon_count(&sensors, &0), 15 - 2);
+        Ok(())
+    }
+
+    #[test]
+    fn single_cell_coverage() -> Result<()> {
+        let sensors = vec![sensor((0, 0), (0, 2))];
+        assert_eq!(cannot_contain_beacon_count(&sensors, &2), 0);
+        assert_eq!(cannot_contain_beacon_count(&sensors, &-2), 1);
+        assert_eq!(cannot_contain_beacon_count(&sensors, &3), 0);
+        Ok(())
+    }
+
     #[test]
     fn example_part_2() -> Result<()> {
-        let sensors = load_input("day_15_example");
-        let point = first_gap(&sensors, &20).unwrap();
-        println!("{point:#?}");
-        let result = point.x * 4000000 + point.y;
-        assert_eq!(result, 56000011);
+        let sensors: Vec<Sensor> = load_input("day_15_example")?;
+
+        for strategy in [GapStrategy::RowScan, GapStrategy::Boundaries] {
+            let point = first_gap(&sensors, &20, strategy).unwrap();
+            assert_eq!(point.tuning_frequency(), Some(56000011));
+        }
+
         Ok(())
     }
 
     #[test]
     fn part_2() -> Result<()> {
-        let sensors = load_input("day_15");
-        let point = first_gap(&sensors, &4000000).unwrap();
-        let result = point.x as i64 * 4000000 + point.y as i64;
-        assert_eq!(result, 11557863040754);
+        let sensors: Vec<Sensor> = load_input("day_15")?;
+        let point = first_gap(&sensors, &4000000, GapStrategy::Boundaries).unwrap();
+        assert_eq!(point.tuning_frequency(), Some(11557863040754));
+        Ok(())
+    }
+
+    #[test]
+    fn strategies_agree() -> Result<()> {
+        let sensors: Vec<Sensor> = load_input("day_15")?;
+        let by_rows = first_gap(&sensors, &4000000, GapStrategy::RowScan);
+        let by_boundaries = first_gap(&sensors, &4000000, GapStrategy::Boundaries);
+        assert_eq!(by_rows, by_boundaries);
+        Ok(())
+    }
+
+    #[test]
+    fn rotated_example() -> Result<()> {
+        let sensors: Vec<Sensor> = load_input("day_15_example")?;
+        let rotated = RotatedCoverage::from_sensors(&sensors);
+        let (min, max) = (Point::new(0, 0), Point::new(20, 20));
+
+        assert_eq!(rotated.uncovered_in(&min, &max), Some(1));
+        assert_eq!(
+            rotated.gaps(&min, &max).collect::<Vec<_>>(),
+            vec![Point::new(14, 11)]
+        );
+        assert_eq!(
+            rotated.covered_in(&Point::new(-100, 10), &Point::new(100, 10)),
+            Some(coverage(&sensors, &10).len())
+        );
+        Ok(())
+    }
+
+    // Sensors scattered around a 30x30 square near the origin, each with its beacon at most
+    // `reach` away along both axes.
+    fn random_sensors(rng: &mut Rng, count: usize, reach: usize) -> Vec<Sensor> {
+        (0..count)
+            .map(|_| {
+                let location = Point::new(
+                    rng.gen_range(0..30) as i64 - 5,
+                    rng.gen_range(0..30) as i64 - 5,
+                );
+                let dx = rng.gen_range(0..2 * reach + 1) as i64 - reach as i64;
+                let dy = rng.gen_range(0..2 * reach + 1) as i64 - reach as i64;
+                Sensor {
+                    location,
+                    closest_beacon: Point::new(location.x + dx, location.y + dy),
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn boundaries_find_wide_gaps() -> Result<()> {
+        let sensors: Vec<Sensor> = [
+            ((5, 5), (10, 14)),
+            ((20, 20), (12, 16)),
+            ((0, -3), (7, 1)),
+            ((23, 6), (12, 3)),
+            ((1, 19), (5, 6)),
+            ((18, -2), (9, -5)),
+        ]
+        .map(|(location, closest_beacon)| Sensor {
+            location: Point::from(location),
+            closest_beacon: Point::from(closest_beacon),
+        })
+        .into();
+
+        let gap = first_gap(&sensors, &20, GapStrategy::Boundaries).unwrap();
+        assert!(!is_covered(&sensors, &gap));
+        assert!((13..=14).contains(&gap.x) && (12..=13).contains(&gap.y));
+        Ok(())
+    }
+
+    #[test]
+    fn strategies_match_brute_force() -> Result<()> {
+        let mut rng = Rng::new(42);
+
+        for _ in 0..5000 {
+            // A dozen medium-sized diamonds leave gaps of all shapes between them.
+            let count = rng.gen_range(8..15);
+            let sensors = random_sensors(&mut rng, count, 10);
+            let max = 20;
+
+            let uncovered = (0..=max)
+                .flat_map(|y| (0..=max).map(move |x| Point::new(x, y)))
+                .any(|point| !is_covered(&sensors, &point));
+
+            for strategy in [GapStrategy::RowScan, GapStrategy::Boundaries] {
+                let gap = first_gap(&sensors, &max, strategy);
+                assert_eq!(gap.is_some(), uncovered, "{strategy:?} on {sensors:?}");
+                if let Some(gap) = gap {
+                    assert!(!is_covered(&sensors, &gap));
+                    assert!((0..=max).contains(&gap.x) && (0..=max).contains(&gap.y));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn rotated_matches_brute_force() -> Result<()> {
+        let mut rng = Rng::new(15);
+
+        for _ in 0..50 {
+            let count = rng.gen_range(1..6);
+            let sensors = random_sensors(&mut rng, count, 15);
+
+            let coverage = RotatedCoverage::from_sensors(&sensors);
+            let (min, max) = (Point::new(0, 3), Point::new(17, 20));
+
+            let area = (-60..90)
+                .flat_map(|y| (-60..90).map(move |x| Point::new(x, y)))
+                .filter(|point| is_covered(&sensors, point))
+                .count();
+            assert_eq!(coverage.area(), Some(area as u128));
+
+            let mut expected: Vec<Point> = (min.y..=max.y)
+                .flat_map(|y| (min.x..=max.x).map(move |x| Point::new(x, y)))
+                .filter(|point| !is_covered(&sensors, point))
+                .collect();
+            let mut gaps: Vec<Point> = coverage.gaps(&min, &max).collect();
+            expected.sort_by_key(|point| (point.x, point.y));
+            gaps.sort_by_key(|point| (point.x, point.y));
+
+            assert_eq!(gaps, expected);
+            assert_eq!(
+                coverage.uncovered_in(&min, &max),
+                Some(expected.len() as u128)
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn rotated_large_boxes() -> Result<()> {
+        let sensors: Vec<Sensor> = load_input("day_15")?;
+        let coverage = RotatedCoverage::from_sensors(&sensors);
+        let (min, max) = (Point::new(0, 0), Point::new(4000000, 4000000));
+
+        assert_eq!(coverage.uncovered_in(&min, &max), Some(1));
+        assert_eq!(
+            coverage.gaps(&min, &max).collect::<Vec<_>>(),
+            first_gap(&sensors, &4000000, GapStrategy::Boundaries)
+                .into_iter()
+                .collect::<Vec<_>>()
+        );
+
+        let sensors: Vec<Sensor<i128>> = load_input("day_15")?;
+        let coverage = RotatedCoverage::from_sensors(&sensors);
+        let bound = 1_i128 << 100;
+        let (min, max) = (Point::new(-bound, -bound), Point::new(bound, bound));
+
+        assert_eq!(coverage.covered_in(&min, &max), coverage.area());
+        assert_eq!(coverage.gaps(&min, &max).take(3).count(), 3);
+        Ok(())
+    }
+
+    #[test]
+    fn overflowing_coordinates() -> Result<()> {
+        let lines = vec![
+            "Sensor at x=24822222222222223411, y=39222222222222202983: closest beacon is at x=22895722222222222229, y=36322223423423423785".to_owned(),
+        ];
+
+        let error = parse_sensors::<i64>(&lines).unwrap_err();
+        assert!(format!("{error:#}").contains("line 1"));
+        assert!(format!("{error:#}").contains("too large"));
+
+        let sensors = parse_sensors::<i128>(&lines)?;
+        assert_eq!(sensors[0].location.x, 24822222222222223411);
+
+        let lines = vec![format!(
+            "Sensor at x={}, y=0: closest beacon is at x=0, y=0",
+            i64::MAX
+        )];
+        let error = parse_sensors::<i64>(&lines).unwrap_err();
+        assert!(format!("{error:#}").contains("64-bit"));
+
+        let lines = vec![format!(
+            "Sensor at x=0, y=0: closest beacon is at x={}, y=0",
+            1_i128 << 126
+        )];
+        let sensors = parse_sensors::<i128>(&lines)?;
+        assert_eq!(cannot_contain_beacon_count(&sensors, &0), 1_u128 << 127);
+        Ok(())
+    }
+
+    #[test]
+    fn wide_coordinates() -> Result<()> {
+        let offset: i128 = 10_i128.pow(30);
+        let sensors: Vec<Sensor<i128>> = load_input::<i64>("day_15_example")?
+            .iter()
+            .map(|sensor| {
+                let shift =
+                    |point: &Point| Point::new(point.x as i128 + offset, point.y as i128 + offset);
+                Sensor {
+                    location: shift(&sensor.location),
+                    closest_beacon: shift(&sensor.closest_beacon),
+                }
+            })
+            .collect();
+
+        assert_eq!(cannot_contain_beacon_count(&sensors, &(offset + 10)), 26);
+
+        let (min, max) = (
+            Point::new(offset, offset),
+            Point::new(offset + 20, offset + 20),
+        );
+        let rotated = RotatedCoverage::from_sensors(&sensors);
+        assert_eq!(
+            rotated.gaps(&min, &max).collect::<Vec<_>>(),
+            vec![Point::new(offset + 14, offset + 11)]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn svg_example() -> Result<()> {
+        let sensors: Vec<Sensor> = load_input("day_15_example")?;
+        let svg = render_svg(
+            &sensors,
+            &SvgOptions {
+                width: 400.0,
+                height: 300.0,
+                row: Some(10),
+                gap: first_gap(&sensors, &20, GapStrategy::Boundaries),
+            },
+        );
+
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.ends_with("</svg>\n"));
+        assert_eq!(svg.matches("<polygon").count(), 14);
+        assert_eq!(svg.matches("r=\"3\"").count(), 14);
+        assert_eq!(svg.matches("width=\"6\"").count(), 6);
+        assert_eq!(svg.matches("<line").count(), 1);
+        assert_eq!(svg.matches("r=\"8\"").count(), 1);
+
+        let corners: Vec<f64> = svg
+            .split("points=\"")
+            .skip(1)
+            .flat_map(|rest| rest.split('"').next().unwrap().split([' ', ',']))
+            .map(|value| value.parse().unwrap())
+            .collect();
+        assert_eq!(corners.len(), 14 * 4 * 2);
+        assert!(corners.iter().all(|value| (0.0..=400.0).contains(value)));
+        Ok(())
+    }
+
+    #[test]
+    fn field_example() -> Result<()> {
+        let field: SensorField = SensorField::load("day_15_example")?;
+
+        assert!(!field.is_covered(&Point::new(14, 11)));
+        assert_eq!(field.covering(&Point::new(14, 11)).count(), 0);
+        assert_eq!(field.covering(&Point::new(8, 7)).count(), 1);
+        assert_eq!(
+            field.nearest_uncovered(&Point::new(14, 11)),
+            Some(Point::new(14, 11))
+        );
+        assert_eq!(field.row_coverage(&10), coverage(&field.sensors, &10));
+        Ok(())
+    }
+
+    #[test]
+    fn field_matches_brute_force() -> Result<()> {
+        let mut rng = Rng::new(47);
+
+        for _ in 0..50 {
+            let field: SensorField = random_sensors(&mut rng, 5, 15).into();
+            let cells = (-60..90).flat_map(|y| (-60..90).map(move |x| Point::new(x, y)));
+
+            for x in [-3, 0, 7, 20] {
+                let column: Vec<i64> = (-60..90)
+                    .filter(|y| field.is_covered(&Point::new(x, *y)))
+                    .collect();
+                let expected: IntervalSet<i64> = column.iter().map(|y| *y..=*y).collect();
+                assert_eq!(field.column_coverage(&x), expected);
+            }
+
+            for point in [Point::new(0, 0), Point::new(10, 12), Point::new(24, 3)] {
+                let nearest = field.nearest_uncovered(&point).unwrap();
+                let dist = cells
+                    .clone()
+                    .filter(|cell| !field.is_covered(cell))
+                    .map(|cell| cell.dist(&point))
+                    .min()
+                    .unwrap();
+
+                assert!(!field.is_covered(&nearest));
+                assert_eq!(nearest.dist(&point), dist);
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn gap_in_corner() -> Result<()> {
+        let mut sensors = vec![Sensor {
+            location: Point::new(10, 10),
+            closest_beacon: Point::new(0, 1),
+        }];
+
+        for strategy in [GapStrategy::RowScan, GapStrategy::Boundaries] {
+            assert_eq!(first_gap(&sensors, &10, strategy), Some(Point::new(0, 0)));
+        }
+
+        sensors.push(Sensor {
+            location: Point::new(0, 0),
+            closest_beacon: Point::new(0, 1),
+        });
+
+        for strategy in [GapStrategy::RowScan, GapStrategy::Boundaries] {
+            assert_eq!(first_gap(&sensors, &10, strategy), None);
+        }
+
         Ok(())
     }
 }
@@ -1,7 +1,79 @@
+use std::{env, path::Path};
+
+use anyhow::{anyhow, Result};
+
 pub mod day_12;
 pub mod day_13;
 pub mod day_14;
 pub mod day_15;
 pub mod utils;
 
-fn main() {}
+const USAGE: &str = "Usage: days_12_and_on day_12
+       days_12_and_on bench_day_12 <width> <height> <seed>
+       days_12_and_on animate_day_14 <input> <fps> <grains_per_frame> [floor]
+       days_12_and_on bench_day_14 <input>
+       days_12_and_on frames_day_14 <input> <directory> <grains_per_frame> <scale> [floor]
+       days_12_and_on bench_day_15 <input> <max>
+       days_12_and_on svg_day_15 <input> <output> <width> <height> <row> <max>
+       days_12_and_on query_day_15 <input> (covering <x> <y> | nearest <x> <y> | row <y> | column <x>)";
+
+fn main() -> Result<()> {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+
+    match args.as_slice() {
+        ["day_12"] => day_12::day_12(),
+        ["bench_day_12", width, height, seed] => day_12::bench_day_12(day_12::HeightmapGenerator {
+            width: width.parse()?,
+            height: height.parse()?,
+            seed: seed.parse()?,
+            reachable: true,
+        }),
+        ["animate_day_14", file_name, fps, grains_per_frame, rest @ ..] => {
+            let config = match rest {
+                [] => day_14::SandConfig::default(),
+                ["floor"] => day_14::SandConfig::with_floor(),
+                _ => return Err(anyhow!(USAGE)),
+            };
+            let mut world = day_14::load_input(file_name)?.with_config(config)?;
+
+            day_14::animate(
+                &mut world,
+                day_14::AnimationOptions {
+                    frames_per_second: fps.parse()?,
+                    grains_per_frame: grains_per_frame.parse()?,
+                    colour: true,
+                },
+            )
+        }
+        ["bench_day_14", file_name] => day_14::bench_day_14(file_name),
+        ["frames_day_14", file_name, directory, grains_per_frame, scale, rest @ ..] => {
+            let config = match rest {
+                [] => day_14::SandConfig::default(),
+                ["floor"] => day_14::SandConfig::with_floor(),
+                _ => return Err(anyhow!(USAGE)),
+            };
+            let mut world = day_14::load_input(file_name)?.with_config(config)?;
+
+            let frames = day_14::export_frames(
+                &mut world,
+                Path::new(directory),
+                grains_per_frame.parse()?,
+                scale.parse()?,
+            )?;
+            println!("Wrote {frames} frames to {directory}");
+            Ok(())
+        }
+        ["bench_day_15", file_name, max] => day_15::bench_day_15(file_name, max.parse()?),
+        ["svg_day_15", file_name, output, width, height, row, max] => day_15::svg_day_15(
+            file_name,
+            Path::new(output),
+            width.parse()?,
+            height.parse()?,
+            row.parse()?,
+            max.parse()?,
+        ),
+        ["query_day_15", file_name, query @ ..] => day_15::query_day_15(file_name, query),
+        _ => Err(anyhow!(USAGE)),
+    }
+}
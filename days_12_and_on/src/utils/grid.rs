@@ -3,8 +3,11 @@ use std::{
     ops::{Index, IndexMut},
 };
 
+use serde::{Deserialize, Serialize};
+
 const NEIGHBOR_OFFSETS: [[i32; 2]; 4] = [[0, 1], [1, 0], [0, -1], [-1, 0]];
 
+#[derive(Clone)]
 pub struct Grid<T>
 where
     T: PartialEq,
@@ -79,6 +82,14 @@ where
         Some(&self.items[position.row * self.width + position.col])
     }
 
+    pub fn get_mut(&mut self, position: &Position) -> Option<&mut T> {
+        if position.row >= self.height() || position.col >= self.width {
+            return None;
+        }
+
+        Some(&mut self.items[position.row * self.width + position.col])
+    }
+
     pub fn find(&self, target: &T) -> Option<Position> {
         for (i, row) in self.rows().into_iter().enumerate() {
             for (j, item) in row.iter().enumerate() {
@@ -147,7 +158,7 @@ impl Debug for Grid<bool> {
     }
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Position {
     pub row: usize,
     pub col: usize,
@@ -1,4 +1,5 @@
 pub mod grid;
+pub mod rng;
 
 use std::fs::read_to_string;
 
@@ -0,0 +1,60 @@
+use std::ops::Range;
+
+// SplitMix64, so generated inputs are reproducible from a seed without pulling in `rand`.
+#[derive(Debug, Clone)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    pub fn gen_range(&mut self, range: Range<usize>) -> usize {
+        if range.is_empty() {
+            panic!("Cannot generate a value from an empty range");
+        }
+
+        range.start + (self.next_u64() % (range.len() as u64)) as usize
+    }
+
+    pub fn gen_bool(&mut self, probability: f64) -> bool {
+        (self.next_u64() >> 11) as f64 / ((1u64 << 53) as f64) < probability
+    }
+
+    pub fn shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() {
+            let j = self.gen_range(0..(i + 1));
+            items.swap(i, j);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Rng;
+
+    #[test]
+    fn same_seed_same_sequence() {
+        let mut a = Rng::new(42);
+        let mut b = Rng::new(42);
+        let left: Vec<u64> = (0..8).map(|_| a.next_u64()).collect();
+        let right: Vec<u64> = (0..8).map(|_| b.next_u64()).collect();
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn gen_range_stays_in_bounds() {
+        let mut rng = Rng::new(7);
+        assert!((0..1000).all(|_| (3..10).contains(&rng.gen_range(3..10))));
+    }
+}
@@ -0,0 +1,312 @@
+use std::{
+    collections::BTreeMap,
+    iter::from_fn,
+    ops::{Range, RangeInclusive},
+};
+
+use num_traits::PrimInt;
+
+// A set of integers, stored as disjoint inclusive ranges keyed by their start. Ranges that
+// overlap or touch are always merged, so `[1..=3, 4..=6]` is stored as `[1..=6]`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IntervalSet<T: PrimInt> {
+    ranges: BTreeMap<T, T>,
+}
+
+impl<T: PrimInt> Default for IntervalSet<T> {
+    fn default() -> Self {
+        Self {
+            ranges: BTreeMap::new(),
+        }
+    }
+}
+
+// Whether a range ending at `end` overlaps or touches one starting at `start`.
+fn reaches<T: PrimInt>(end: T, start: T) -> bool {
+    end >= start || end.checked_add(&T::one()) == Some(start)
+}
+
+impl<T: PrimInt> IntervalSet<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Empty ranges (where start > end) are ignored.
+    pub fn insert(&mut self, range: RangeInclusive<T>) {
+        let (mut start, mut end) = (*range.start(), *range.end());
+
+        if start > end {
+            return;
+        }
+
+        if let Some((&other_start, &other_end)) = self.ranges.range(..=start).next_back() {
+            if reaches(other_end, start) {
+                self.ranges.remove(&other_start);
+                start = other_start;
+                end = end.max(other_end);
+            }
+        }
+
+        while let Some((&other_start, &other_end)) = self.ranges.range(start..).next() {
+            if !reaches(end, other_start) {
+                break;
+            }
+
+            self.ranges.remove(&other_start);
+            end = end.max(other_end);
+        }
+
+        self.ranges.insert(start, end);
+    }
+
+    pub fn insert_half_open(&mut self, range: Range<T>) {
+        if range.start < range.end {
+            self.insert(range.start..=(range.end - T::one()));
+        }
+    }
+
+    pub fn contains(&self, value: &T) -> bool {
+        self.ranges
+            .range(..=*value)
+            .next_back()
+            .is_some_and(|(_, end)| end >= value)
+    }
+
+    pub fn contains_range(&self, range: &RangeInclusive<T>) -> bool {
+        range.is_empty()
+            || self
+                .ranges
+                .range(..=*range.start())
+                .next_back()
+                .is_some_and(|(_, end)| end >= range.end())
+    }
+
+    pub fn overlaps(&self, range: &RangeInclusive<T>) -> bool {
+        !range.is_empty()
+            && self
+                .ranges
+                .range(..=*range.end())
+                .next_back()
+                .is_some_and(|(_, end)| end >= range.start())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    // Number of integers in the set. The width of a range is taken as an unsigned difference, so
+    // only a set holding every i128 or u128 is too big to count.
+    pub fn len(&self) -> u128 {
+        self.iter()
+            .map(|range| {
+                let (start, end) = (*range.start(), *range.end());
+                let width = match (start.to_i128(), end.to_i128()) {
+                    (Some(start), Some(end)) => (end as u128).wrapping_sub(start as u128),
+                    _ => end.to_u128().unwrap() - start.to_u128().unwrap(),
+                };
+                width + 1
+            })
+            .sum()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = RangeInclusive<T>> + '_ {
+        self.ranges.iter().map(|(start, end)| *start..=*end)
+    }
+
+    pub fn min(&self) -> Option<T> {
+        self.ranges.keys().next().copied()
+    }
+
+    pub fn max(&self) -> Option<T> {
+        self.ranges.values().next_back().copied()
+    }
+
+    pub fn union(&self, other: &Self) -> Self {
+        let mut union = self.clone();
+        other.iter().for_each(|range| union.insert(range));
+        union
+    }
+
+    pub fn intersection(&self, other: &Self) -> Self {
+        let mut intersection = Self::new();
+        let mut left = self.iter().peekable();
+        let mut right = other.iter().peekable();
+
+        while let (Some(a), Some(b)) = (left.peek(), right.peek()) {
+            let start = *a.start().max(b.start());
+            let end = *a.end().min(b.end());
+            intersection.insert(start..=end);
+
+            if a.end() < b.end() {
+                left.next();
+            } else {
+                right.next();
+            }
+        }
+
+        intersection
+    }
+
+    pub fn difference(&self, other: &Self) -> Self {
+        match (self.min(), self.max()) {
+            (Some(min), Some(max)) => self.intersection(&other.complement(min..=max)),
+            _ => Self::new(),
+        }
+    }
+
+    // Everything inside `bounds` that isn't in the set.
+    pub fn complement(&self, bounds: RangeInclusive<T>) -> Self {
+        self.gaps(bounds).collect()
+    }
+
+    // The uncovered ranges inside `bounds`, in order.
+    pub fn gaps(&self, bounds: RangeInclusive<T>) -> impl Iterator<Item = RangeInclusive<T>> + '_ {
+        let (start, end) = (*bounds.start(), *bounds.end());
+        let mut cursor = (start <= end).then_some(start);
+        let first_key = self
+            .ranges
+            .range(..=start)
+            .next_back()
+            .map_or(start, |(key, _)| *key);
+        let mut ranges = self.ranges.range(first_key..);
+
+        from_fn(move || loop {
+            let from = cursor?;
+
+            let Some((&range_start, &range_end)) = ranges.next() else {
+                cursor = None;
+                return Some(from..=end);
+            };
+
+            if range_end < from {
+                continue;
+            }
+
+            if range_start > end {
+                cursor = None;
+                return Some(from..=end);
+            }
+
+            cursor = range_end.checked_add(&T::one()).filter(|next| *next <= end);
+
+            if range_start > from {
+                return Some(from..=(range_start - T::one()));
+            }
+        })
+    }
+}
+
+impl<T: PrimInt> From<RangeInclusive<T>> for IntervalSet<T> {
+    fn from(value: RangeInclusive<T>) -> Self {
+        let mut set = Self::new();
+        set.insert(value);
+        set
+    }
+}
+
+impl<T: PrimInt> FromIterator<RangeInclusive<T>> for IntervalSet<T> {
+    fn from_iter<I: IntoIterator<Item = RangeInclusive<T>>>(iter: I) -> Self {
+        let mut set = Self::new();
+        iter.into_iter().for_each(|range| set.insert(range));
+        set
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::IntervalSet;
+
+    fn ranges(set: &IntervalSet<i32>) -> Vec<(i32, i32)> {
+        set.iter()
+            .map(|range| (*range.start(), *range.end()))
+            .collect()
+    }
+
+    #[test]
+    fn insert_merges_overlapping_and_touching() {
+        let mut set = IntervalSet::new();
+        set.insert(10..=12);
+        set.insert(1..=3);
+        set.insert(4..=5);
+        set.insert(20..=25);
+        set.insert(11..=21);
+        let (start, end) = (8, 7);
+        set.insert(start..=end);
+
+        assert_eq!(ranges(&set), vec![(1, 5), (10, 25)]);
+        assert_eq!(set.len(), 21);
+    }
+
+    #[test]
+    fn half_open_and_inclusive() {
+        let mut set = IntervalSet::new();
+        set.insert_half_open(0..5);
+        set.insert_half_open(7..7);
+
+        assert_eq!(ranges(&set), vec![(0, 4)]);
+        assert!(set.contains(&4));
+        assert!(!set.contains(&5));
+    }
+
+    #[test]
+    fn queries() {
+        let set: IntervalSet<i32> = [1..=5, 10..=15].into_iter().collect();
+
+        assert!(set.contains_range(&(2..=5)));
+        assert!(!set.contains_range(&(4..=10)));
+        assert!(set.overlaps(&(5..=9)));
+        assert!(!set.overlaps(&(6..=9)));
+    }
+
+    #[test]
+    fn set_operations() {
+        let a: IntervalSet<i32> = [0..=10, 20..=30].into_iter().collect();
+        let b: IntervalSet<i32> = [5..=25].into_iter().collect();
+
+        assert_eq!(ranges(&a.union(&b)), vec![(0, 30)]);
+        assert_eq!(ranges(&a.intersection(&b)), vec![(5, 10), (20, 25)]);
+        assert_eq!(ranges(&a.difference(&b)), vec![(0, 4), (26, 30)]);
+        assert_eq!(ranges(&b.difference(&a)), vec![(11, 19)]);
+        assert_eq!(
+            ranges(&a.complement(-5..=35)),
+            vec![(-5, -1), (11, 19), (31, 35)]
+        );
+    }
+
+    #[test]
+    fn gaps_within_bounds() {
+        let set: IntervalSet<i32> = [0..=3, 6..=8, 12..=20].into_iter().collect();
+
+        let gaps: Vec<_> = set.gaps(2..=14).collect();
+        assert_eq!(gaps, vec![4..=5, 9..=11]);
+
+        let gaps: Vec<_> = set.gaps(-2..=-1).collect();
+        assert_eq!(gaps, vec![-2..=-1]);
+
+        assert_eq!(set.gaps(0..=3).next(), None);
+    }
+
+    #[test]
+    fn extreme_values() {
+        let mut set = IntervalSet::new();
+        set.insert(u8::MIN..=10);
+        set.insert(250..=u8::MAX);
+        set.insert(11..=249);
+
+        assert_eq!(set.len(), 256);
+        assert_eq!(set.gaps(0..=u8::MAX).next(), None);
+
+        let full: IntervalSet<i64> = IntervalSet::from(i64::MIN..=i64::MAX);
+        assert_eq!(full.len(), u64::MAX as u128 + 1);
+
+        let wide: IntervalSet<i128> = IntervalSet::from(i128::MIN..=i128::MAX - 1);
+        assert_eq!(wide.len(), u128::MAX);
+
+        let mut halves: IntervalSet<i128> = IntervalSet::from(i128::MIN..=-2);
+        halves.insert(1..=i128::MAX);
+        assert_eq!(halves.len(), u128::MAX - 1);
+
+        let top: IntervalSet<u128> = IntervalSet::from(1..=u128::MAX);
+        assert_eq!(top.len(), u128::MAX);
+    }
+}